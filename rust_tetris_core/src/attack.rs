@@ -0,0 +1,241 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::scoring::{LineClear, SpinType};
+
+pub trait AttackTable {
+    /// Lines sent for `clear`, `b2b` being the number of difficult clears
+    /// chained right before it.
+    fn attack(&self, clear: &LineClear, b2b: u32) -> u32;
+}
+
+fn base_attack(clear: &LineClear, table: &[u32; 10]) -> u32 {
+    let index = match (clear.spin, clear.lines) {
+        (_, 0) => return 0,
+        (SpinType::None, lines) => lines.min(4) - 1,
+        (SpinType::Mini, 1) => 4,
+        (SpinType::Mini, _) => 5,
+        (SpinType::Full, lines) => 5 + lines.min(3),
+    };
+    table[index as usize]
+}
+
+// single, double, triple, tetris, mini single, mini double, TSS, TSD, TST, unused
+const GUIDELINE_BASE: [u32; 10] = [0, 1, 2, 4, 0, 1, 2, 4, 6, 0];
+const GUIDELINE_COMBO: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Tetris 99 style: fixed B2B bonus and combo table.
+pub struct GuidelineAttack;
+
+impl AttackTable for GuidelineAttack {
+    fn attack(&self, clear: &LineClear, b2b: u32) -> u32 {
+        if clear.lines == 0 {
+            return 0;
+        }
+
+        let b2b_bonus = if b2b > 0 && clear.is_difficult() {
+            1
+        } else {
+            0
+        };
+        let combo = GUIDELINE_COMBO[(clear.combo as usize).min(GUIDELINE_COMBO.len() - 1)];
+        let perfect_clear = if clear.perfect_clear { 10 } else { 0 };
+
+        base_attack(clear, &GUIDELINE_BASE) + b2b_bonus + combo + perfect_clear
+    }
+}
+
+/// TETR.IO style: logarithmic B2B bonus and multiplicative combos.
+pub struct TetrIoAttack;
+
+impl AttackTable for TetrIoAttack {
+    fn attack(&self, clear: &LineClear, b2b: u32) -> u32 {
+        if clear.lines == 0 {
+            return 0;
+        }
+
+        let base = base_attack(clear, &GUIDELINE_BASE) as f64;
+        let mut attack = base;
+
+        if b2b > 0 && clear.is_difficult() {
+            let level = 1.0 + (b2b as f64 * 0.8).ln_1p();
+            attack += level.floor();
+            if b2b > 1 {
+                attack += level.fract() / 3.0;
+            }
+        }
+
+        if clear.combo > 0 {
+            attack *= 1.0 + 0.25 * clear.combo as f64;
+            if base == 0.0 && clear.combo > 1 {
+                attack = attack.max((1.0 + 1.25 * clear.combo as f64).ln());
+            }
+        }
+
+        let perfect_clear = if clear.perfect_clear { 10 } else { 0 };
+        attack.floor() as u32 + perfect_clear
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CustomAttackTable {
+    pub single: u32,
+    pub double: u32,
+    pub triple: u32,
+    pub tetris: u32,
+    pub t_spin_mini_single: u32,
+    pub t_spin_mini_double: u32,
+    pub t_spin_single: u32,
+    pub t_spin_double: u32,
+    pub t_spin_triple: u32,
+    pub back_to_back: u32,
+    // indexed by combo count, the last entry is used for longer combos
+    pub combo: Vec<u32>,
+    pub perfect_clear: u32,
+}
+
+impl AttackTable for CustomAttackTable {
+    fn attack(&self, clear: &LineClear, b2b: u32) -> u32 {
+        if clear.lines == 0 {
+            return 0;
+        }
+
+        let base = base_attack(
+            clear,
+            &[
+                self.single,
+                self.double,
+                self.triple,
+                self.tetris,
+                self.t_spin_mini_single,
+                self.t_spin_mini_double,
+                self.t_spin_single,
+                self.t_spin_double,
+                self.t_spin_triple,
+                0,
+            ],
+        );
+        let b2b_bonus = if b2b > 0 && clear.is_difficult() {
+            self.back_to_back
+        } else {
+            0
+        };
+        let combo = self
+            .combo
+            .get(clear.combo as usize)
+            .or_else(|| self.combo.last())
+            .copied()
+            .unwrap_or(0);
+        let perfect_clear = if clear.perfect_clear {
+            self.perfect_clear
+        } else {
+            0
+        };
+
+        base + b2b_bonus + combo + perfect_clear
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(tag = "type")]
+pub enum AttackTableType {
+    #[default]
+    Guideline,
+    TetrIo,
+    Custom(CustomAttackTable),
+}
+
+impl AttackTableType {
+    pub fn table(&self) -> &dyn AttackTable {
+        match self {
+            AttackTableType::Guideline => &GuidelineAttack,
+            AttackTableType::TetrIo => &TetrIoAttack,
+            AttackTableType::Custom(table) => table,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: u32, spin: SpinType) -> LineClear {
+        LineClear {
+            lines,
+            spin,
+            back_to_back: false,
+            combo: 0,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn test_guideline_attack() {
+        let table = GuidelineAttack;
+
+        assert_eq!(table.attack(&clear(1, SpinType::None), 0), 0);
+        assert_eq!(table.attack(&clear(4, SpinType::None), 0), 4);
+        assert_eq!(table.attack(&clear(4, SpinType::None), 3), 5);
+        assert_eq!(table.attack(&clear(2, SpinType::Full), 1), 5);
+        assert_eq!(table.attack(&clear(2, SpinType::Mini), 0), 1);
+        assert_eq!(table.attack(&clear(0, SpinType::Full), 2), 0);
+
+        let combo = LineClear {
+            combo: 4,
+            ..clear(2, SpinType::None)
+        };
+        assert_eq!(table.attack(&combo, 0), 1 + 2);
+
+        let perfect_clear = LineClear {
+            perfect_clear: true,
+            ..clear(1, SpinType::None)
+        };
+        assert_eq!(table.attack(&perfect_clear, 0), 10);
+    }
+
+    #[test]
+    fn test_tetrio_attack() {
+        let table = TetrIoAttack;
+
+        assert_eq!(table.attack(&clear(4, SpinType::None), 0), 4);
+        assert_eq!(table.attack(&clear(4, SpinType::None), 1), 5);
+        assert_eq!(table.attack(&clear(3, SpinType::Full), 10), 9);
+
+        let combo = LineClear {
+            combo: 4,
+            ..clear(4, SpinType::None)
+        };
+        assert_eq!(table.attack(&combo, 0), 8);
+
+        let singles = LineClear {
+            combo: 8,
+            ..clear(1, SpinType::None)
+        };
+        assert_eq!(table.attack(&singles, 0), 2);
+    }
+
+    #[test]
+    fn test_custom_table() {
+        let table = CustomAttackTable {
+            single: 1,
+            double: 2,
+            triple: 3,
+            tetris: 5,
+            t_spin_mini_single: 1,
+            t_spin_mini_double: 2,
+            t_spin_single: 3,
+            t_spin_double: 5,
+            t_spin_triple: 7,
+            back_to_back: 2,
+            combo: vec![0, 1, 2],
+            perfect_clear: 6,
+        };
+
+        let combo = LineClear {
+            combo: 9,
+            ..clear(2, SpinType::Full)
+        };
+        assert_eq!(table.attack(&combo, 1), 5 + 2 + 2);
+        assert_eq!(table.attack(&clear(1, SpinType::None), 0), 1);
+        assert_eq!(table.attack(&clear(1, SpinType::Mini), 4), 1 + 2);
+    }
+}
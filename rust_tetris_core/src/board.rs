@@ -22,31 +22,36 @@ pub fn not_playable_piece_to_cell() -> TetrisCell {
     TetrisCell::FilledCell(TetrisPieceType::NotPlayable)
 }
 
+/// Occupancy is kept as one bit per cell (bit `j` is column `j`) next to the
+/// cell colors, so row and collision checks never have to look at the colors.
+#[derive(Clone)]
 pub struct TetrisBoard {
     pub rows: isize,
     pub cols: isize,
-    data: Vec<Vec<TetrisCell>>,
-    empty_row_proto: Vec<TetrisCell>,
+    occupancy: Vec<u64>,
+    cells: Vec<TetrisCell>,
+    full_row: u64,
 }
 
 impl TetrisBoard {
     pub fn new(rows: isize, cols: isize) -> Self {
-        let mut board = TetrisBoard {
+        assert!(cols <= 64, "Boards wider than 64 columns are not supported");
+
+        TetrisBoard {
             rows,
             cols,
-            data: Vec::with_capacity(rows as usize),
-            empty_row_proto: vec![TetrisCell::EmptyCell; cols as usize],
-        };
-
-        for _ in 0..rows {
-            board.data.push(board.empty_row_proto.clone());
+            occupancy: vec![0; rows as usize],
+            cells: vec![TetrisCell::EmptyCell; (rows * cols) as usize],
+            full_row: if cols == 64 { !0 } else { (1 << cols) - 1 },
         }
+    }
 
-        board
+    fn index(&self, i: isize, j: isize) -> usize {
+        (i * self.cols + j) as usize
     }
 
     pub fn get(&self, i: isize, j: isize) -> TetrisCell {
-        self.data[i as usize][j as usize]
+        self.cells[self.index(i, j)]
     }
 
     pub fn is_in_bounds(&self, i: isize, j: isize) -> bool {
@@ -57,7 +62,7 @@ impl TetrisBoard {
         if !self.is_in_bounds(i, j) {
             false
         } else {
-            is_filled(self.data[i as usize][j as usize])
+            self.occupancy[i as usize] & (1 << j) != 0
         }
     }
 
@@ -70,17 +75,69 @@ impl TetrisBoard {
     }
 
     pub fn set_val(&mut self, i: isize, j: isize, b: TetrisCell) {
-        self.data[i as usize][j as usize] = b;
+        let index = self.index(i, j);
+        self.cells[index] = b;
+
+        if is_filled(b) {
+            self.occupancy[i as usize] |= 1 << j;
+        } else {
+            self.occupancy[i as usize] &= !(1 << j);
+        }
+    }
+
+    pub fn row_bits(&self, i: isize) -> u64 {
+        self.occupancy[i as usize]
+    }
+
+    pub fn full_row_mask(&self) -> u64 {
+        self.full_row
     }
 
     pub fn is_complete(&self, i: isize) -> bool {
-        self.data[i as usize].iter().all(|&cell| is_filled(cell))
+        self.occupancy[i as usize] == self.full_row
     }
 
     pub fn is_empty(&self) -> bool {
-        self.data
+        self.occupancy.iter().all(|&row| row == 0)
+    }
+
+    /// Tests `piece` placed with its top left corner at (`row`, `col`).
+    /// Cells outside the board count as collisions.
+    pub fn collides(&self, piece: &TetrisBoard, row: isize, col: isize) -> bool {
+        piece
+            .occupancy
             .iter()
-            .all(|row| row.iter().all(|&cell| !is_filled(cell)))
+            .enumerate()
+            .filter(|&(_, &mask)| mask != 0)
+            .any(|(i, &mask)| {
+                let r = row + i as isize;
+                if r < 0 || r >= self.rows {
+                    return true;
+                }
+
+                match self.shift_mask(mask, col) {
+                    Some(shifted) => shifted & self.occupancy[r as usize] != 0,
+                    None => true,
+                }
+            })
+    }
+
+    fn shift_mask(&self, mask: u64, col: isize) -> Option<u64> {
+        if col >= 0 {
+            let shifted = mask.checked_shl(col as u32).unwrap_or(0);
+            if shifted >> col == mask && shifted & !self.full_row == 0 {
+                Some(shifted)
+            } else {
+                None
+            }
+        } else {
+            let shift = (-col) as u32;
+            if shift < 64 && mask & ((1 << shift) - 1) == 0 {
+                Some(mask >> shift)
+            } else {
+                None
+            }
+        }
     }
 
     pub fn completed_rows(&mut self) -> Vec<(isize, isize)> {
@@ -89,9 +146,7 @@ impl TetrisBoard {
         let mut from = None;
         let mut to: Option<isize> = None;
 
-        for i in (0..self.data.len()).rev() {
-            let i = i as isize;
-
+        for i in (0..self.rows).rev() {
             if self.is_complete(i) {
                 if from.is_none() {
                     from = Some(i);
@@ -117,12 +172,10 @@ impl TetrisBoard {
         }
     }
 
-    pub fn rows(&self) -> impl Iterator<Item = &Vec<TetrisCell>> {
-        self.data.iter()
-    }
-
-    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut Vec<TetrisCell>> {
-        self.data.iter_mut()
+    /// The cells of each row, top first. There is no mutable access to the
+    /// rows, cells are changed with `set_val` so that the occupancy follows.
+    pub fn rows(&self) -> impl Iterator<Item = &[TetrisCell]> {
+        self.cells.chunks(self.cols as usize)
     }
 
     pub fn remove_row(&mut self, row: isize) {
@@ -134,32 +187,57 @@ impl TetrisBoard {
             return;
         }
 
-        let offset = from - to;
-        let last_to_copy = self.rows;
-        let last_to_copy_rev = self.rows - last_to_copy;
+        let offset = (from - to) as usize;
+        let kept = (to + 1) as usize;
+        let cols = self.cols as usize;
+
+        self.occupancy.copy_within(0..kept, offset);
+        self.occupancy[..offset].fill(0);
+
+        self.cells.copy_within(0..kept * cols, offset * cols);
+        self.cells[..offset * cols].fill(TetrisCell::EmptyCell);
+    }
 
-        for i in ((to + 1)..=from).rev() {
-            self.data[i as usize] = self.empty_row_proto.clone();
+    /// Shifts every row up by one and puts `row` at the bottom, returns true if
+    /// filled cells were pushed out of the top.
+    pub fn push_row(&mut self, row: &[TetrisCell]) -> bool {
+        let overflow = self.occupancy.first().is_some_and(|&bits| bits != 0);
+        let cols = self.cols as usize;
+        let last = self.rows - 1;
+
+        self.occupancy.copy_within(1.., 0);
+        self.cells.copy_within(cols.., 0);
+        for (j, &cell) in row.iter().enumerate().take(cols) {
+            self.set_val(last, j as isize, cell);
         }
-        for i in (last_to_copy_rev..=to).rev() {
-            let row_to_clear = i as usize + offset as usize;
-            self.data.swap(i as usize, row_to_clear);
+
+        overflow
+    }
+
+    pub fn push_rows(&mut self, rows: &[Vec<TetrisCell>]) -> bool {
+        let mut overflow = false;
+        for row in rows {
+            overflow |= self.push_row(row);
         }
+        overflow
     }
 
     pub fn get_first_set_col(&self) -> Option<isize> {
-        (0..self.cols)
-            .flat_map(|j| (0..self.rows).map(move |i| (i, j)))
-            .find(|&(i, j)| self.is_set(i, j))
-            .map(|(_, j)| j)
+        let used = self.occupancy.iter().fold(0, |acc, &row| acc | row);
+        if used == 0 {
+            None
+        } else {
+            Some(used.trailing_zeros() as isize)
+        }
     }
 
     pub fn get_last_set_col(&self) -> Option<isize> {
-        (0..self.cols)
-            .rev()
-            .flat_map(|j| (0..self.rows).map(move |i| (i, j)))
-            .find(|&(i, j)| self.is_set(i, j))
-            .map(|(_, j)| j)
+        let used = self.occupancy.iter().fold(0, |acc, &row| acc | row);
+        if used == 0 {
+            None
+        } else {
+            Some(63 - used.leading_zeros() as isize)
+        }
     }
 }
 
@@ -228,6 +306,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_remove_rows_keeps_colors() {
+        let mut board = TetrisBoard::new(4, 2);
+        board.set(1, 0, TetrisPieceType::NotPlayable);
+        board.set(2, 0, TetrisPieceType::NotPlayable);
+        board.set(2, 1, TetrisPieceType::NotPlayable);
+
+        board.remove_row(2);
+
+        assert_eq!(board.get(2, 0), not_playable_piece_to_cell());
+        assert_eq!(board.get(2, 1), TetrisCell::EmptyCell);
+        assert_eq!(board.row_bits(2), 0b01);
+        assert_eq!(board.row_bits(0), 0);
+        assert_eq!(board.row_bits(1), 0);
+    }
+
+    #[test]
+    fn test_push_rows() {
+        let mut board = TetrisBoard::new(3, 2);
+        board.set(2, 0, TetrisPieceType::NotPlayable);
+
+        let row = vec![TetrisCell::EmptyCell, not_playable_piece_to_cell()];
+        assert!(!board.push_rows(std::slice::from_ref(&row)));
+
+        assert_eq!(board.row_bits(1), 0b01);
+        assert_eq!(board.row_bits(2), 0b10);
+        assert_eq!(board.get(2, 1), not_playable_piece_to_cell());
+
+        assert!(!board.push_row(&row));
+        assert!(board.push_row(&row));
+    }
+
+    #[test]
+    fn test_collides() {
+        let mut board = TetrisBoard::new(4, 4);
+        board.set(3, 1, TetrisPieceType::NotPlayable);
+
+        let mut piece = TetrisBoard::new(2, 2);
+        piece.set(1, 0, TetrisPieceType::NotPlayable);
+        piece.set(1, 1, TetrisPieceType::NotPlayable);
+
+        assert!(!board.collides(&piece, 0, 0));
+        assert!(!board.collides(&piece, -1, 0));
+        assert!(board.collides(&piece, 2, 0));
+        assert!(!board.collides(&piece, 2, 2));
+        assert!(board.collides(&piece, 0, 3));
+        assert!(board.collides(&piece, 0, -1));
+        assert!(board.collides(&piece, 3, 2));
+    }
+
     #[test]
     fn test_remove_rows3() {
         let mut board = TetrisBoard::new(5, 3);
@@ -0,0 +1,745 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    attack::AttackTableType,
+    board::TetrisBoard,
+    engine::Game,
+    engine_structs::{GameInput, GameKey, GameParams, TetrisPieceWithPosition},
+    enums::PlayableTetrisPieceType,
+    movegen::{generate_moves, GeneratedMove},
+    pieces::TetrisPiece,
+    planner::{apply_input, hard_dropped, plan, Placement, PlannedInput, PlannerOptions},
+    scoring::LineClear,
+    spin::SpinRule,
+};
+
+// deeper wells are no better for a tetris, only riskier
+const MAX_WELL_DEPTH: isize = 4;
+
+/// Weights of the evaluation, features are penalties when negative and
+/// rewards when positive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BotWeights {
+    // sum of the column heights
+    pub height: f64,
+    pub max_height: f64,
+    // empty cells with a filled cell above them
+    pub holes: f64,
+    // sum of the height differences between neighbour columns
+    pub bumpiness: f64,
+    pub well_depth: f64,
+    // spots shaped for a T-spin double
+    pub t_slots: f64,
+    // per line sent
+    pub attack: f64,
+    // per line cleared, whatever the attack
+    pub lines: f64,
+    pub back_to_back: f64,
+}
+
+impl Default for BotWeights {
+    fn default() -> Self {
+        BotWeights {
+            height: -0.2,
+            max_height: -0.5,
+            holes: -4.0,
+            bumpiness: -0.3,
+            well_depth: 0.5,
+            t_slots: 1.5,
+            attack: 1.0,
+            lines: -0.5,
+            back_to_back: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BotParams {
+    pub weights: BotWeights,
+    // pieces of the preview placed after the current one
+    pub lookahead: usize,
+    pub use_hold: bool,
+    // placements kept at each depth of the search
+    pub beam_width: usize,
+    // placements evaluated per decision, 0 for no limit
+    pub node_budget: usize,
+    // milliseconds of thinking per decision, 0 for no limit
+    pub think_time: u64,
+}
+
+impl Default for BotParams {
+    fn default() -> Self {
+        BotParams {
+            weights: BotWeights::default(),
+            lookahead: 2,
+            use_hold: true,
+            beam_width: 16,
+            node_budget: 20000,
+            think_time: 0,
+        }
+    }
+}
+
+/// Board features the evaluation is made of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardFeatures {
+    pub height: isize,
+    pub max_height: isize,
+    pub holes: isize,
+    pub bumpiness: isize,
+    pub well_depth: isize,
+    pub t_slots: isize,
+}
+
+impl BoardFeatures {
+    pub fn of(board: &TetrisBoard) -> Self {
+        let cols = board.cols as usize;
+        let mut heights = vec![0; cols];
+        let mut covered = 0;
+        let mut holes = 0;
+
+        for i in 0..board.rows {
+            let bits = board.row_bits(i);
+            let tops = bits & !covered;
+            for (j, height) in heights.iter_mut().enumerate() {
+                if tops & (1 << j) != 0 {
+                    *height = board.rows - i;
+                }
+            }
+            holes += (covered & !bits).count_ones() as isize;
+            covered |= bits;
+        }
+
+        let bumpiness = heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum();
+        let well_depth = (0..cols)
+            .map(|j| {
+                let left = if j == 0 { None } else { heights.get(j - 1) };
+                let side = match (left, heights.get(j + 1)) {
+                    (Some(&l), Some(&r)) => l.min(r),
+                    (Some(&h), None) | (None, Some(&h)) => h,
+                    (None, None) => 0,
+                };
+                (side - heights[j]).clamp(0, MAX_WELL_DEPTH)
+            })
+            .max()
+            .unwrap_or(0);
+
+        BoardFeatures {
+            height: heights.iter().sum(),
+            max_height: heights.iter().copied().max().unwrap_or(0),
+            holes,
+            bumpiness,
+            well_depth,
+            t_slots: t_slots(board),
+        }
+    }
+
+    pub fn evaluate(&self, weights: &BotWeights) -> f64 {
+        weights.height * self.height as f64
+            + weights.max_height * self.max_height as f64
+            + weights.holes * self.holes as f64
+            + weights.bumpiness * self.bumpiness as f64
+            + weights.well_depth * self.well_depth as f64
+            + weights.t_slots * self.t_slots as f64
+    }
+}
+
+// three empty cells over an empty one between two filled ones, with an
+// overhang on a side: a T pointing down fits with three corners filled
+fn t_slots(board: &TetrisBoard) -> isize {
+    let mut slots = 0;
+    for i in 1..board.rows - 1 {
+        if board.row_bits(i + 1) == 0 {
+            continue;
+        }
+        for j in 1..board.cols - 1 {
+            let fits = !board.is_set(i, j - 1)
+                && !board.is_set(i, j)
+                && !board.is_set(i, j + 1)
+                && !board.is_set(i + 1, j)
+                && !board.is_set(i - 1, j);
+            let corners = board.is_set(i + 1, j - 1) && board.is_set(i + 1, j + 1);
+            let overhang = board.is_set(i - 1, j - 1) || board.is_set(i - 1, j + 1);
+            if fits && corners && overhang {
+                slots += 1;
+            }
+        }
+    }
+    slots
+}
+
+/// What the bot sees of a game, new pieces appearing at `spawn` with the
+/// rotation system of `piece`.
+#[derive(Clone)]
+pub struct BotState {
+    pub board: TetrisBoard,
+    pub piece: TetrisPieceWithPosition,
+    pub hold: Option<PlayableTetrisPieceType>,
+    pub can_hold: bool,
+    pub queue: Vec<PlayableTetrisPieceType>,
+    pub spawn: (isize, isize),
+    pub b2b: u32,
+    pub combo: u32,
+}
+
+impl BotState {
+    pub fn of(game: &Game) -> Option<Self> {
+        Some(BotState {
+            board: game.board().clone(),
+            piece: game.piece()?.clone(),
+            hold: game.hold_piece().map(|h| h.piece.piece_type),
+            can_hold: game.can_swap_hold(),
+            queue: game.next_pieces().map(|p| p.piece_type).collect(),
+            spawn: game.spawn_position(),
+            b2b: game.back_to_back(),
+            combo: game.current_combo(),
+        })
+    }
+
+    fn spawned(&self, piece: PlayableTetrisPieceType) -> TetrisPieceWithPosition {
+        let current = self.piece.tetris_piece_ref();
+        let piece = TetrisPiece::with_rotation_system(piece, current.rotation_system)
+            .with_kicks_180(current.kicks_180);
+        TetrisPieceWithPosition::new(self.spawn.0, self.spawn.1, piece)
+    }
+
+    /// Inputs bringing the piece in play, or the one swapped in when
+    /// `hold` is set, to `placement`.
+    pub fn plan_move(
+        &self,
+        hold: bool,
+        placement: Placement,
+        options: &PlannerOptions,
+    ) -> Option<BotMove> {
+        let piece = if hold {
+            self.spawned(placement.piece)
+        } else {
+            self.piece.clone()
+        };
+        let inputs = plan(&self.board, &piece, &placement, options)?;
+        Some(BotMove {
+            hold,
+            placement,
+            inputs,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BotMove {
+    pub hold: bool,
+    pub placement: Placement,
+    // for the piece in play once held, if `hold` is set
+    pub inputs: Vec<PlannedInput>,
+}
+
+#[derive(Clone)]
+struct Node {
+    board: TetrisBoard,
+    // None once the known queue is used up
+    current: Option<PlayableTetrisPieceType>,
+    hold: Option<PlayableTetrisPieceType>,
+    // index in the queue of the piece after `current`
+    next: usize,
+    b2b: u32,
+    combo: u32,
+    // rewards of the clears made so far
+    reward: f64,
+    score: f64,
+    first: Option<(bool, Placement)>,
+}
+
+struct Budget {
+    nodes: usize,
+    max_nodes: usize,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    fn spend(&mut self) -> bool {
+        if self.max_nodes > 0 && self.nodes >= self.max_nodes {
+            return false;
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return false;
+        }
+        self.nodes += 1;
+        true
+    }
+}
+
+/// Beam search over the placements of the current piece and the preview,
+/// hold included, scoring each resulting board with weighted features.
+pub struct Bot {
+    params: BotParams,
+    spin_rule: SpinRule,
+    attack_table: AttackTableType,
+    options: PlannerOptions,
+}
+
+impl Bot {
+    pub fn new(params: BotParams, rules: &GameParams) -> Self {
+        Bot {
+            params,
+            spin_rule: rules.spin_rule,
+            attack_table: rules.attack_table.clone(),
+            options: PlannerOptions::default(),
+        }
+    }
+
+    pub fn params(&self) -> &BotParams {
+        &self.params
+    }
+
+    pub fn think(&self, state: &BotState) -> Option<BotMove> {
+        let mut budget = Budget {
+            nodes: 0,
+            max_nodes: self.params.node_budget,
+            deadline: match self.params.think_time {
+                0 => None,
+                ms => Some(Instant::now() + Duration::from_millis(ms)),
+            },
+        };
+
+        let root = Node {
+            board: state.board.clone(),
+            current: Some(state.piece.tetris_piece_ref().piece_type),
+            hold: state.hold,
+            next: 0,
+            b2b: state.b2b,
+            combo: state.combo,
+            reward: 0.0,
+            score: 0.0,
+            first: None,
+        };
+
+        let mut beam = vec![root];
+        let mut candidates = vec![];
+        for _ in 0..=self.params.lookahead {
+            let mut children = vec![];
+            for node in &beam {
+                self.expand(state, node, &mut children, &mut budget);
+            }
+            if children.is_empty() {
+                break;
+            }
+
+            children.sort_by(|a, b| b.score.total_cmp(&a.score));
+            if candidates.is_empty() {
+                candidates = children.clone();
+            }
+            children.truncate(self.params.beam_width.max(1));
+            beam = children;
+        }
+
+        // the best line first, then the other first placements in case
+        // the planner cannot reach one found by the search
+        let mut tried = HashSet::new();
+        beam.iter()
+            .chain(&candidates)
+            .filter_map(|node| node.first)
+            .filter(|&first| tried.insert(first))
+            .into_iter()
+            .find_map(|(hold, placement)| state.plan_move(hold, placement, &self.options))
+    }
+
+    fn expand(&self, state: &BotState, node: &Node, children: &mut Vec<Node>, budget: &mut Budget) {
+        let current = match node.current {
+            Some(current) => current,
+            None => return,
+        };
+        let is_root = node.first.is_none();
+
+        // (held, piece played, piece in hold after it, next queue index)
+        let mut choices = vec![(false, current, node.hold, node.next)];
+        if self.params.use_hold && (!is_root || state.can_hold) {
+            match node.hold {
+                Some(held) if held != current => {
+                    choices.push((true, held, Some(current), node.next))
+                }
+                Some(_) => {}
+                None => {
+                    if let Some(&piece) = state.queue.get(node.next) {
+                        choices.push((true, piece, Some(current), node.next + 1));
+                    }
+                }
+            }
+        }
+
+        for (held, piece, hold, next) in choices {
+            let spawned = if is_root && !held {
+                state.piece.clone()
+            } else {
+                state.spawned(piece)
+            };
+
+            for generated in generate_moves(&node.board, &spawned, &self.options) {
+                if !budget.spend() {
+                    return;
+                }
+                let mut child = self.place(node, &generated);
+                child.hold = hold;
+                child.current = state.queue.get(next).copied();
+                child.next = next + 1;
+                child.first = node.first.or(Some((held, generated.placement())));
+
+                // a piece that cannot spawn ends the game
+                let blocked = child
+                    .current
+                    .is_some_and(|p| state.spawned(p).collides(&child.board));
+                if !blocked {
+                    children.push(child);
+                }
+            }
+        }
+    }
+
+    fn place(&self, node: &Node, generated: &GeneratedMove) -> Node {
+        let spin = generated.spin(&node.board, self.spin_rule);
+        let mut board = node.board.clone();
+        generated.piece.finalize_on(&mut board);
+
+        let ranges = board.completed_rows();
+        let lines: u32 = ranges.iter().map(|r| (r.0 - r.1) as u32).sum();
+        let perfect_clear =
+            lines > 0 && (0..board.rows).all(|i| board.row_bits(i) == 0 || board.is_complete(i));
+        board.remove_ranges(ranges);
+
+        let combo = if lines == 0 { 0 } else { node.combo + 1 };
+        let clear = LineClear {
+            lines,
+            spin,
+            back_to_back: node.b2b > 0,
+            combo: combo.saturating_sub(1),
+            perfect_clear,
+        };
+        let attack = self.attack_table.table().attack(&clear, node.b2b);
+        let b2b = match lines {
+            0 => node.b2b,
+            _ if clear.is_difficult() => node.b2b + 1,
+            _ => 0,
+        };
+
+        let weights = &self.params.weights;
+        let reward = node.reward + weights.attack * attack as f64 + weights.lines * lines as f64;
+        let chain = if b2b > 0 { weights.back_to_back } else { 0.0 };
+        let score = reward + chain + BoardFeatures::of(&board).evaluate(weights);
+
+        Node {
+            board,
+            current: None,
+            hold: node.hold,
+            next: node.next,
+            b2b,
+            combo,
+            reward,
+            score,
+            first: node.first,
+        }
+    }
+}
+
+/// Decides where the pieces of a game go.
+pub trait MoveSource {
+    /// Move for the piece in play, asked again on the next ticks while None.
+    fn next_move(&mut self, game: &Game) -> Option<BotMove>;
+
+    /// Called when a new game starts.
+    fn reset(&mut self) {}
+}
+
+impl MoveSource for Bot {
+    fn next_move(&mut self, game: &Game) -> Option<BotMove> {
+        BotState::of(game).and_then(|state| self.think(&state))
+    }
+}
+
+/// Plays a game with the moves of a source, one key press or release per tick.
+pub struct BotPlayer {
+    source: Box<dyn MoveSource>,
+    // placed pieces count the plan was made at
+    planned_at: Option<u64>,
+    placement: Option<Placement>,
+    hold: bool,
+    inputs: VecDeque<PlannedInput>,
+    pressed: Option<GameKey>,
+    options: PlannerOptions,
+}
+
+impl BotPlayer {
+    pub fn new<S: MoveSource + 'static>(source: S) -> Self {
+        BotPlayer {
+            source: Box::new(source),
+            planned_at: None,
+            placement: None,
+            hold: false,
+            inputs: VecDeque::new(),
+            pressed: None,
+            options: PlannerOptions::default(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.planned_at = None;
+        self.placement = None;
+        self.hold = false;
+        self.inputs.clear();
+        self.pressed = None;
+        self.source.reset();
+    }
+
+    /// Inputs for the next tick of `game`.
+    pub fn inputs(&mut self, game: &Game) -> Vec<GameInput> {
+        if let Some(key) = self.pressed.take() {
+            return vec![GameInput::Released(key)];
+        }
+        let piece = match game.piece() {
+            Some(piece) => piece,
+            None => return vec![],
+        };
+
+        // the piece locked before the plan was done, by gravity or an undo
+        if self.planned_at != Some(game.placed_pieces()) {
+            let bot_move = match self.source.next_move(game) {
+                Some(bot_move) => bot_move,
+                None => return vec![],
+            };
+            self.planned_at = Some(game.placed_pieces());
+            self.placement = Some(bot_move.placement);
+            self.hold = bot_move.hold;
+            self.inputs = bot_move.inputs.into();
+        }
+
+        let key = if self.hold {
+            self.hold = false;
+            Some(GameKey::Hold)
+        } else {
+            if !self.on_path(piece, game.board()) {
+                self.replan(game);
+            }
+            self.next_key(piece, game.board())
+        };
+        self.pressed = key;
+        key.map(GameInput::Pressed).into_iter().collect()
+    }
+
+    // whether the inputs left still bring the piece to the placement, gravity
+    // being ignored by the planner
+    fn on_path(&self, piece: &TetrisPieceWithPosition, board: &TetrisBoard) -> bool {
+        let mut piece = piece.clone();
+        for &input in &self.inputs {
+            apply_input(&mut piece, input, board);
+        }
+        self.placement == Some(Placement::of(&hard_dropped(&piece, board)))
+    }
+
+    fn replan(&mut self, game: &Game) {
+        let placement = match self.placement {
+            Some(placement) => placement,
+            None => return,
+        };
+        let bot_move =
+            BotState::of(game).and_then(|state| state.plan_move(false, placement, &self.options));
+        self.inputs = match bot_move {
+            Some(bot_move) => bot_move.inputs.into(),
+            // out of reach, the piece is dropped where it is
+            None => VecDeque::from(vec![PlannedInput::HardDrop]),
+        };
+    }
+
+    fn next_key(
+        &mut self,
+        piece: &TetrisPieceWithPosition,
+        board: &TetrisBoard,
+    ) -> Option<GameKey> {
+        while let Some(&input) = self.inputs.front() {
+            // auto repeat is made of taps, until the piece stops moving
+            let key = match input {
+                PlannedInput::DasLeft if piece.clone().try_move_left(board) => {
+                    return Some(GameKey::Left)
+                }
+                PlannedInput::DasRight if piece.clone().try_move_right(board) => {
+                    return Some(GameKey::Right)
+                }
+                PlannedInput::DasLeft | PlannedInput::DasRight => None,
+                PlannedInput::Left => Some(GameKey::Left),
+                PlannedInput::Right => Some(GameKey::Right),
+                PlannedInput::RotateCw => Some(GameKey::NextRotation),
+                PlannedInput::RotateCcw => Some(GameKey::PrevRotation),
+                PlannedInput::Rotate180 => Some(GameKey::Rotate180),
+                PlannedInput::SoftDrop => Some(GameKey::SonicDrop),
+                PlannedInput::HardDrop => Some(GameKey::HardDrop),
+            };
+            self.inputs.pop_front();
+            if key.is_some() {
+                return key;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::TetrisPieceType;
+    use crate::scoring::GravityCurve;
+
+    fn board(rows: &[&str]) -> TetrisBoard {
+        let mut board = TetrisBoard::new(22, 10);
+        let offset = board.rows - rows.len() as isize;
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                if c == '*' {
+                    board.set(
+                        offset + i as isize,
+                        j as isize,
+                        TetrisPieceType::NotPlayable,
+                    );
+                }
+            }
+        }
+        board
+    }
+
+    fn state(rows: &[&str], queue: &[PlayableTetrisPieceType]) -> BotState {
+        BotState {
+            board: board(rows),
+            piece: TetrisPieceWithPosition::new(0, 4, TetrisPiece::new(queue[0])),
+            hold: None,
+            can_hold: true,
+            queue: queue[1..].to_vec(),
+            spawn: (0, 4),
+            b2b: 0,
+            combo: 0,
+        }
+    }
+
+    #[test]
+    fn test_board_features() {
+        let features = BoardFeatures::of(&board(&["*  *      ", "*  *    * ", "**  * ****"]));
+
+        assert_eq!(features.height, 3 + 1 + 3 + 1 + 1 + 1 + 2 + 1);
+        assert_eq!(features.max_height, 3);
+        assert_eq!(features.holes, 1);
+        assert_eq!(features.bumpiness, 2 + 1 + 3 + 2 + 1 + 1 + 1 + 1);
+        assert_eq!(features.well_depth, 1);
+        assert_eq!(features.t_slots, 0);
+
+        let slot = BoardFeatures::of(&board(&["**        ", "*   ******", "** *******"]));
+        assert_eq!(slot.t_slots, 1);
+    }
+
+    #[test]
+    fn test_fills_the_well() {
+        let rows = ["********* ", "********* ", "********* ", "********* "];
+        let state = state(&rows, &[PlayableTetrisPieceType::I]);
+        let bot = Bot::new(BotParams::default(), &GameParams::default());
+
+        let bot_move = bot.think(&state).unwrap();
+        assert!(!bot_move.hold);
+
+        let mut piece = state.piece.clone();
+        for &input in &bot_move.inputs {
+            apply_input(&mut piece, input, &state.board);
+        }
+        let mut board = state.board.clone();
+        hard_dropped(&piece, &board).finalize_on(&mut board);
+        let ranges = board.completed_rows();
+        board.remove_ranges(ranges);
+        assert!(board.is_empty());
+    }
+
+    #[test]
+    fn test_holds_for_a_better_piece() {
+        let rows = ["********* ", "********* ", "********* ", "********* "];
+        let queue = [PlayableTetrisPieceType::S, PlayableTetrisPieceType::I];
+        let params = BotParams {
+            lookahead: 0,
+            ..BotParams::default()
+        };
+        let bot = Bot::new(params.clone(), &GameParams::default());
+
+        let bot_move = bot.think(&state(&rows, &queue)).unwrap();
+        assert!(bot_move.hold);
+        assert_eq!(bot_move.placement.piece, PlayableTetrisPieceType::I);
+
+        let no_hold = BotParams {
+            use_hold: false,
+            ..params
+        };
+        let bot_move = Bot::new(no_hold, &GameParams::default())
+            .think(&state(&rows, &queue))
+            .unwrap();
+        assert!(!bot_move.hold);
+        assert_eq!(bot_move.placement.piece, PlayableTetrisPieceType::S);
+    }
+
+    #[test]
+    fn test_node_budget_limits_search() {
+        let params = BotParams {
+            node_budget: 1,
+            ..BotParams::default()
+        };
+        let bot = Bot::new(params, &GameParams::default());
+
+        let bot_move = bot.think(&state(&[], &[PlayableTetrisPieceType::T]));
+
+        assert!(bot_move.is_some());
+    }
+
+    #[test]
+    fn test_player_plays_a_game() {
+        let game_params = GameParams {
+            seed: Some(3),
+            ..GameParams::default()
+        };
+        let bot_params = BotParams {
+            lookahead: 1,
+            beam_width: 4,
+            ..BotParams::default()
+        };
+        let mut player = BotPlayer::new(Bot::new(bot_params, &game_params));
+        let mut game = Game::new(game_params);
+        game.start();
+
+        while game.placed_pieces() < 50 && game.game_over().is_none() {
+            let inputs = player.inputs(&game);
+            game.tick(&inputs);
+        }
+
+        assert_eq!(game.game_over(), None);
+        assert!(game.lines() >= 10);
+    }
+
+    #[test]
+    fn test_player_plays_at_20g() {
+        let game_params = GameParams {
+            seed: Some(3),
+            gravity_curve: GravityCurve::Fixed,
+            gravity: 20.0,
+            ..GameParams::default()
+        };
+        let bot_params = BotParams {
+            lookahead: 1,
+            beam_width: 4,
+            ..BotParams::default()
+        };
+        let mut player = BotPlayer::new(Bot::new(bot_params, &game_params));
+        let mut game = Game::new(game_params);
+        game.start();
+
+        while game.placed_pieces() < 50 && game.game_over().is_none() {
+            let inputs = player.inputs(&game);
+            game.tick(&inputs);
+        }
+
+        assert_eq!(game.game_over(), None);
+        assert!(game.lines() >= 10);
+    }
+}
@@ -4,6 +4,15 @@ use std::collections::HashMap;
 
 pub type Kick = (isize, isize);
 
+/// Kick used by a successful rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotationKick {
+    pub offset: Kick,
+    /// Fifth test of a 90° rotation (TST and fin kicks), which turns a T-spin
+    /// Mini into a full T-spin.
+    pub fifth_test: bool,
+}
+
 pub(crate) static I_KICKS: [[Kick; 5]; 8] = [
     [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
     [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
@@ -24,6 +33,88 @@ pub(crate) static DEFAULT_KICKS: [[Kick; 5]; 8] = [
     [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
     [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
 ];
+pub(crate) static SRS_PLUS_I_KICKS: [[Kick; 5]; 8] = [
+    [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)],
+];
+pub(crate) static TETRIO_180_KICKS: [[Kick; 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+pub(crate) static NULLPOMINO_180_KICKS: [[Kick; 12]; 4] = [
+    [
+        (0, 0),
+        (1, 0),
+        (2, 0),
+        (1, 1),
+        (2, 1),
+        (-1, 0),
+        (-2, 0),
+        (-1, 1),
+        (-2, 1),
+        (0, -1),
+        (3, 0),
+        (-3, 0),
+    ],
+    [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (-1, 1),
+        (-1, 2),
+        (0, -1),
+        (0, -2),
+        (-1, -1),
+        (-1, -2),
+        (1, 0),
+        (0, 3),
+        (0, -3),
+    ],
+    [
+        (0, 0),
+        (-1, 0),
+        (-2, 0),
+        (-1, -1),
+        (-2, -1),
+        (1, 0),
+        (2, 0),
+        (1, -1),
+        (2, -1),
+        (0, 1),
+        (-3, 0),
+        (3, 0),
+    ],
+    [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 1),
+        (1, 2),
+        (0, -1),
+        (0, -2),
+        (1, -1),
+        (1, -2),
+        (-1, 0),
+        (0, 3),
+        (0, -3),
+    ],
+];
+pub(crate) static NULLPOMINO_I_180_KICKS: [[Kick; 6]; 4] = [
+    [(0, 0), (-1, 0), (-2, 0), (1, 0), (2, 0), (0, 1)],
+    [(0, 0), (0, 1), (0, 2), (0, -1), (0, -2), (-1, 0)],
+    [(0, 0), (1, 0), (2, 0), (-1, 0), (-2, 0), (0, -1)],
+    [(0, 0), (0, 1), (0, 2), (0, -1), (0, -2), (1, 0)],
+];
+pub(crate) static ARS_KICKS: [Kick; 3] = [(0, 0), (1, 0), (-1, 0)];
+pub(crate) static NO_KICKS: [Kick; 1] = [(0, 0)];
 
 lazy_static! {
     pub(crate) static ref NEXT_ROTATIONS: HashMap<TetrisPieceRotation, TetrisPieceRotation> = {
@@ -42,4 +133,36 @@ lazy_static! {
         m.insert(TetrisPieceRotation::RIGHT, TetrisPieceRotation::ZERO);
         m
     };
+    pub(crate) static ref OPPOSITE_ROTATIONS: HashMap<TetrisPieceRotation, TetrisPieceRotation> = {
+        let mut m = HashMap::new();
+        m.insert(TetrisPieceRotation::ZERO, TetrisPieceRotation::TWO);
+        m.insert(TetrisPieceRotation::RIGHT, TetrisPieceRotation::LEFT);
+        m.insert(TetrisPieceRotation::TWO, TetrisPieceRotation::ZERO);
+        m.insert(TetrisPieceRotation::LEFT, TetrisPieceRotation::RIGHT);
+        m
+    };
+}
+
+pub fn is_not_empty(kick: Kick) -> bool {
+    kick.0 != 0 || kick.1 != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_not_empty() {
+        let mut kick = (1, 0);
+        assert!(is_not_empty(kick));
+
+        kick = (0, 1);
+        assert!(is_not_empty(kick));
+
+        kick = (1, 1);
+        assert!(is_not_empty(kick));
+
+        kick = (0, 0);
+        assert!(!is_not_empty(kick));
+    }
 }
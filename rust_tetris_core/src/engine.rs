@@ -0,0 +1,1896 @@
+use std::collections::VecDeque;
+
+use log::{debug, trace};
+
+use crate::{
+    board::TetrisBoard,
+    constants::{is_not_empty, RotationKick},
+    engine_structs::{
+        GameInput, GameKey, GameOverReason, GameParams, HoldTetrisPiece, LockDownMode, ScoreType,
+        SocdPolicy, TetrisPieceWithPosition, TetrisUpdateResult,
+    },
+    enums::{PlayableTetrisPieceType, TetrisPieceType},
+    garbage::GarbageQueue,
+    history::{GameSnapshot, History},
+    pieces::TetrisPiece,
+    randomizer::Randomizer,
+    replay::{Replay, ReplayAction, ReplayEvent, REPLAY_VERSION},
+    rotation::RotationSystem,
+    scoring::{guideline_gravity, GravityCurve, LineClear, Scoring, SpinType},
+    spin::detect_spin,
+};
+
+const MAX_LOCK_RESETS: u32 = 15;
+
+#[derive(PartialEq, Eq, Debug)]
+enum Moves {
+    Fall,
+    Rotate,
+    Side,
+}
+
+fn is_piece_key(key: GameKey) -> bool {
+    matches!(
+        key,
+        GameKey::NextRotation
+            | GameKey::PrevRotation
+            | GameKey::Rotate180
+            | GameKey::HardDrop
+            | GameKey::SonicDrop
+            | GameKey::Hold
+    )
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Phase {
+    Falling,
+    // ticks left before the completed rows are removed
+    LineClear(u32),
+    // ticks left before the next piece spawns
+    Entry(u32),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum SideMoves {
+    Left,
+    Right,
+}
+
+pub struct Game {
+    board: TetrisBoard,
+    piece: Option<TetrisPieceWithPosition>,
+    hold_piece: Option<HoldTetrisPiece>,
+    randomizer: Box<dyn Randomizer>,
+    seed: u64,
+    rotation_system: &'static dyn RotationSystem,
+    down_movement_accumulator: f64,
+    side_movement_accumulator: f64,
+    // milliseconds the side key has been held since its first move
+    das_charge: f64,
+    side_tapped: bool,
+    // milliseconds left before auto repeat may move the piece again
+    das_cut: f64,
+    current_gravity: f64,
+    buffer_next_pieces: VecDeque<TetrisPiece>,
+    last_move: Moves,
+    last_score: Option<ScoreType>,
+    phase: Phase,
+    // presses that need a piece, made while waiting for the next one
+    buffered_keys: Vec<GameKey>,
+    held_rotation: Option<GameKey>,
+    hold_held: bool,
+    lock_timer: u32,
+    // moves and rotations made on the ground since the piece reached `lowest_row`
+    lock_resets: u32,
+    lowest_row: isize,
+    b2b_chain: u32,
+    current_combo: u32,
+    scoring: Scoring,
+    soft_dropping: bool,
+    last_kick: Option<RotationKick>,
+    params: GameParams,
+    side_move_to_perform: Option<SideMoves>,
+    // side keys physically held, oldest press first
+    held_sides: Vec<SideMoves>,
+    game_over: Option<GameOverReason>,
+    garbage: GarbageQueue,
+    last_attack: u32,
+    total_attack: u32,
+    outgoing_garbage: u32,
+    placed_pieces: u64,
+    frames: u64,
+    history: Option<History>,
+    events: Vec<ReplayEvent>,
+}
+
+impl Game {
+    pub fn new(params: GameParams) -> Self {
+        let current_gravity = match params.gravity_curve {
+            GravityCurve::Fixed => params.gravity,
+            GravityCurve::Guideline => guideline_gravity(params.start_level, params.tick_rate),
+        };
+        // kept within the TOML integer range, so it can be written to config and replay files
+        let seed = params.seed.unwrap_or_else(|| rand::random::<u32>() as u64);
+        let scoring = Scoring::new(params.start_level);
+        let garbage = GarbageQueue::new(params.garbage.clone(), seed.wrapping_add(1));
+        let history = if params.mode.allows_undo() {
+            Some(History::default())
+        } else {
+            None
+        };
+        Game {
+            board: TetrisBoard::new(
+                (params.visible_height + params.buffer_height) as isize,
+                params.width as isize,
+            ),
+            piece: None,
+            hold_piece: None,
+            randomizer: params.randomizer.build(seed),
+            seed,
+            rotation_system: params.rotation_system.system(),
+            params,
+            down_movement_accumulator: 0.0,
+            side_movement_accumulator: 0.0,
+            current_gravity,
+            current_combo: 0,
+            scoring,
+            soft_dropping: false,
+            buffer_next_pieces: VecDeque::with_capacity(5),
+            last_move: Moves::Fall,
+            last_score: None,
+            b2b_chain: 0,
+            das_charge: 0.0,
+            side_tapped: false,
+            das_cut: 0.0,
+            phase: Phase::Falling,
+            buffered_keys: Vec::new(),
+            held_rotation: None,
+            hold_held: false,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_row: 0,
+            last_kick: None,
+            side_move_to_perform: None,
+            held_sides: Vec::with_capacity(2),
+            game_over: None,
+            garbage,
+            last_attack: 0,
+            total_attack: 0,
+            outgoing_garbage: 0,
+            placed_pieces: 0,
+            frames: 0,
+            history,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.start_with(&[], &[]);
+    }
+
+    pub fn start_with(&mut self, rows: &[&str], pieces: &[PlayableTetrisPieceType]) {
+        self.initial_setup(rows, pieces);
+        self.fill_buffer();
+        self.next_block(None);
+        self.save_placement();
+    }
+
+    fn initial_setup(&mut self, rows: &[&str], pieces: &[PlayableTetrisPieceType]) {
+        let mut row_index = self.board.rows - 1;
+        let mut col_index = 0;
+        for r in rows.iter().rev() {
+            for c in r.chars() {
+                if c != ' ' {
+                    self.board
+                        .set(row_index, col_index, TetrisPieceType::NotPlayable);
+                }
+                col_index += 1;
+            }
+            row_index -= 1;
+            col_index = 0;
+        }
+
+        for piece in pieces {
+            let piece = self.new_piece(*piece);
+            self.buffer_next_pieces.push_front(piece);
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn board(&self) -> &TetrisBoard {
+        &self.board
+    }
+
+    /// Rows above this index belong to the hidden buffer zone.
+    pub fn buffer_rows(&self) -> isize {
+        self.params.buffer_height as isize
+    }
+
+    /// Row and column where new pieces appear.
+    pub fn spawn_position(&self) -> (isize, isize) {
+        ((self.buffer_rows() - 2).max(0), self.board.cols / 2 - 1)
+    }
+
+    pub fn piece(&self) -> Option<&TetrisPieceWithPosition> {
+        self.piece.as_ref()
+    }
+
+    pub fn hold_piece(&self) -> Option<&HoldTetrisPiece> {
+        self.hold_piece.as_ref()
+    }
+
+    pub fn can_swap_hold(&self) -> bool {
+        HoldTetrisPiece::can_swap(&self.hold_piece)
+    }
+
+    pub fn next_pieces(&self) -> impl Iterator<Item = &TetrisPiece> {
+        self.buffer_next_pieces.iter().rev()
+    }
+
+    pub fn last_score(&self) -> Option<ScoreType> {
+        self.last_score
+    }
+
+    pub fn back_to_back(&self) -> u32 {
+        self.b2b_chain.saturating_sub(1)
+    }
+
+    pub fn score(&self) -> u64 {
+        self.scoring.score()
+    }
+
+    pub fn level(&self) -> u32 {
+        self.scoring.level()
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.scoring.lines()
+    }
+
+    pub fn current_combo(&self) -> u32 {
+        self.current_combo
+    }
+
+    pub fn shadow_row(&self) -> Option<isize> {
+        self.piece.as_ref().map(|piece_info| {
+            let mut shadow_row = piece_info.row();
+
+            while !piece_info.collides_on_next_with_row(shadow_row, &self.board) {
+                shadow_row += 1;
+            }
+
+            shadow_row
+        })
+    }
+
+    pub fn receive_garbage(&mut self, lines: u32) {
+        self.record(ReplayAction::ReceiveGarbage(lines));
+        self.garbage.receive(lines);
+    }
+
+    pub fn pending_garbage(&self) -> u32 {
+        self.garbage.pending()
+    }
+
+    pub fn last_attack(&self) -> u32 {
+        self.last_attack
+    }
+
+    pub fn total_attack(&self) -> u32 {
+        self.total_attack
+    }
+
+    pub fn attack_per_minute(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.total_attack as f64 * 60.0 * self.params.tick_rate as f64 / self.frames as f64
+        }
+    }
+
+    /// Lines sent to the opponents since the last call, what is left of the
+    /// attacks once they cancelled the pending garbage.
+    pub fn take_outgoing_garbage(&mut self) -> u32 {
+        std::mem::take(&mut self.outgoing_garbage)
+    }
+
+    pub fn placed_pieces(&self) -> u64 {
+        self.placed_pieces
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn tick_rate(&self) -> u32 {
+        self.params.tick_rate
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.as_ref().is_some_and(History::can_undo)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.as_ref().is_some_and(History::can_redo)
+    }
+
+    /// Goes back to the spawn of the previous piece, returns false if there is
+    /// nothing to undo or undo is disabled by the game mode.
+    pub fn undo(&mut self) -> bool {
+        match self.history.as_mut().and_then(History::undo).cloned() {
+            Some(snapshot) => {
+                self.record(ReplayAction::Undo);
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.history.as_mut().and_then(History::redo).cloned() {
+            Some(snapshot) => {
+                self.record(ReplayAction::Redo);
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn game_over(&self) -> Option<GameOverReason> {
+        self.game_over
+    }
+
+    /// The game so far, with the seed it was started with.
+    pub fn replay(&self) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            frames: self.frames,
+            params: GameParams {
+                seed: Some(self.seed),
+                ..self.params.clone()
+            },
+            events: self.events.clone(),
+        }
+    }
+
+    fn record(&mut self, action: ReplayAction) {
+        self.events.push(ReplayEvent {
+            tick: self.frames,
+            action,
+        });
+    }
+
+    pub fn tick(&mut self, inputs: &[GameInput]) -> TetrisUpdateResult {
+        for &input in inputs {
+            self.record(ReplayAction::Input(input));
+        }
+
+        for &input in inputs {
+            if self.game_over.is_some() {
+                break;
+            }
+            self.handle_input(input);
+        }
+
+        if self.game_over.is_none() {
+            self.frames += 1;
+            self.garbage.tick();
+            self.advance_frame();
+        }
+
+        match self.game_over {
+            Some(reason) => {
+                debug!("Game over: {:?}", reason);
+                TetrisUpdateResult::GameOver { reason }
+            }
+            None => TetrisUpdateResult::Continue,
+        }
+    }
+
+    fn handle_input(&mut self, input: GameInput) {
+        self.track_held_keys(input);
+
+        match input {
+            GameInput::Pressed(key) if is_piece_key(key) => {
+                if self.piece.is_some() {
+                    self.press_piece_key(key);
+                } else {
+                    self.buffered_keys.push(key);
+                }
+            }
+            GameInput::Pressed(GameKey::Left) => self.press_side(SideMoves::Left),
+            GameInput::Pressed(GameKey::Right) => self.press_side(SideMoves::Right),
+            GameInput::Released(GameKey::Left) => self.release_side(SideMoves::Left),
+            GameInput::Released(GameKey::Right) => self.release_side(SideMoves::Right),
+            GameInput::Pressed(GameKey::SoftDrop) => self.start_soft_drop(),
+            GameInput::Released(GameKey::SoftDrop) => self.reset_drop(),
+            _ => {}
+        }
+    }
+
+    /// Keeps track of the rotation and hold keys held down, for IRS and IHS.
+    fn track_held_keys(&mut self, input: GameInput) {
+        match input {
+            GameInput::Pressed(
+                key @ (GameKey::NextRotation | GameKey::PrevRotation | GameKey::Rotate180),
+            ) => self.held_rotation = Some(key),
+            GameInput::Released(key) if self.held_rotation == Some(key) => {
+                self.held_rotation = None
+            }
+            GameInput::Pressed(GameKey::Hold) => self.hold_held = true,
+            GameInput::Released(GameKey::Hold) => self.hold_held = false,
+            _ => {}
+        }
+    }
+
+    fn press_piece_key(&mut self, key: GameKey) {
+        match key {
+            GameKey::NextRotation => self.rotate(TetrisPiece::rotate_piece),
+            GameKey::PrevRotation => self.rotate(TetrisPiece::rotate_piece_prev),
+            GameKey::Rotate180 => self.rotate(TetrisPiece::rotate_piece_180),
+            GameKey::HardDrop => self.hard_drop(),
+            GameKey::SonicDrop => self.sonic_drop(),
+            GameKey::Hold => self.hold(),
+            _ => {}
+        }
+    }
+
+    /// Locks the piece and returns the number of completed rows, which stay on
+    /// the board when there is a line clear delay.
+    fn handle_finalize(&mut self) -> u32 {
+        let piece_with_position = self.piece.as_ref().unwrap();
+        let piece_type = piece_with_position.tetris_piece_ref().piece_type;
+
+        let spin = if self.last_move == Moves::Rotate {
+            detect_spin(
+                piece_with_position,
+                &self.board,
+                self.last_kick,
+                self.params.spin_rule,
+            )
+        } else {
+            SpinType::None
+        };
+        debug!("Spin detected: {:?}", spin);
+
+        piece_with_position.finalize_on(&mut self.board);
+
+        let buffer_rows = self.buffer_rows();
+        if piece_with_position
+            .tetris_piece_ref()
+            .set_cells()
+            .all(|(i, _)| piece_with_position.row() + i < buffer_rows)
+        {
+            self.game_over = Some(GameOverReason::LockOut);
+        }
+
+        let completed_rows_ranges = self.board.completed_rows();
+        let completed_rows: u32 = completed_rows_ranges
+            .iter()
+            .map(|r| (r.0 - r.1) as u32)
+            .sum();
+
+        if completed_rows == 0 {
+            self.current_combo = 0;
+        } else {
+            self.current_combo += 1;
+        }
+
+        self.last_score = match (spin, completed_rows) {
+            (SpinType::Mini, lines) if piece_type != PlayableTetrisPieceType::T => {
+                Some(match lines {
+                    0 => ScoreType::Spin(piece_type),
+                    1 => ScoreType::SpinSingle(piece_type),
+                    2 => ScoreType::SpinDouble(piece_type),
+                    3 => ScoreType::SpinTriple(piece_type),
+                    _ => ScoreType::SpinQuad(piece_type),
+                })
+            }
+            (SpinType::Full, 0) => Some(ScoreType::TSpin),
+            (SpinType::Full, 1) => Some(ScoreType::TSpinSingle),
+            (SpinType::Full, 2) => Some(ScoreType::TSpinDouble),
+            (SpinType::Full, _) => Some(ScoreType::TSpinTriple),
+            (SpinType::Mini, 0) => Some(ScoreType::TSpinMini),
+            (SpinType::Mini, 1) => Some(ScoreType::TSpinMiniSingle),
+            (SpinType::Mini, _) => Some(ScoreType::TSpinMiniDouble),
+            (SpinType::None, 1) => Some(ScoreType::Single),
+            (SpinType::None, 2) => Some(ScoreType::Double),
+            (SpinType::None, 3) => Some(ScoreType::Triple),
+            (SpinType::None, 4) => {
+                debug!("Tetris detected");
+                Some(ScoreType::Tetris)
+            }
+            _ => None,
+        };
+        debug!("Score computed: {:?}", self.last_score);
+
+        let perfect_clear = completed_rows > 0
+            && (0..self.board.rows)
+                .all(|i| self.board.row_bits(i) == 0 || self.board.is_complete(i));
+        if self.params.line_clear_delay == 0 {
+            self.board.remove_ranges(completed_rows_ranges);
+        }
+
+        let clear = LineClear {
+            lines: completed_rows,
+            spin,
+            back_to_back: self.b2b_chain > 0,
+            combo: self.current_combo.saturating_sub(1),
+            perfect_clear,
+        };
+
+        self.last_attack = self
+            .params
+            .attack_table
+            .table()
+            .attack(&clear, self.b2b_chain);
+        self.total_attack += self.last_attack;
+
+        if completed_rows > 0 {
+            self.outgoing_garbage += self.garbage.cancel(self.last_attack);
+        } else if self.garbage.insert_ready(&mut self.board) {
+            self.game_over.get_or_insert(GameOverReason::TopOut);
+        }
+
+        if completed_rows > 0 || spin != SpinType::None {
+            let level = self.scoring.level();
+            let points = self.scoring.line_clear(&clear);
+            debug!("Awarded {} points", points);
+
+            if self.scoring.level() != level {
+                debug!("Level up: {}", self.scoring.level());
+                self.update_gravity();
+            }
+        }
+
+        if completed_rows > 0 {
+            if clear.is_difficult() {
+                self.b2b_chain += 1;
+            } else {
+                self.b2b_chain = 0;
+            }
+        }
+
+        if self.back_to_back() > 0 {
+            debug!("B2B level: {}", self.back_to_back());
+        }
+
+        if clear.perfect_clear {
+            self.last_score = Some(ScoreType::AllClear);
+        }
+
+        completed_rows
+    }
+
+    fn lock_piece(&mut self) {
+        let lines = self.handle_finalize();
+        self.piece = None;
+        self.placed_pieces += 1;
+        self.down_movement_accumulator = 0.0;
+        // the charge built during the delays below is kept for the next piece
+        self.discard_das();
+
+        if lines > 0 && self.params.line_clear_delay > 0 && self.game_over.is_none() {
+            self.phase = Phase::LineClear(self.params.line_clear_delay);
+        } else {
+            self.start_entry();
+        }
+    }
+
+    fn start_entry(&mut self) {
+        if self.params.are > 0 && self.game_over.is_none() {
+            self.phase = Phase::Entry(self.params.are);
+        } else {
+            self.spawn_next();
+        }
+    }
+
+    fn spawn_next(&mut self) {
+        self.phase = Phase::Falling;
+        self.next_block(None);
+        self.save_placement();
+
+        let initial_hold = self.params.ihs && self.hold_held;
+        if initial_hold {
+            self.hold();
+        }
+
+        for key in std::mem::take(&mut self.buffered_keys) {
+            // presses still held were already used by IRS and IHS
+            let initial_rotation = self.params.irs && self.held_rotation == Some(key);
+            if initial_rotation || (initial_hold && key == GameKey::Hold) {
+                continue;
+            }
+            if self.piece.is_some() {
+                self.press_piece_key(key);
+            } else {
+                self.buffered_keys.push(key);
+            }
+        }
+    }
+
+    fn clear_completed_rows(&mut self) {
+        let ranges = self.board.completed_rows();
+        self.board.remove_ranges(ranges);
+    }
+
+    /// Completed rows waiting for the end of the line clear delay.
+    pub fn clearing_rows(&self) -> Vec<isize> {
+        match self.phase {
+            Phase::LineClear(_) => (0..self.board.rows)
+                .filter(|&i| self.board.is_complete(i))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn advance_frame(&mut self) {
+        match self.phase {
+            Phase::Falling => self.advance_piece(),
+            Phase::LineClear(ticks) => {
+                self.charge_das();
+                if ticks > 1 {
+                    self.phase = Phase::LineClear(ticks - 1);
+                } else {
+                    self.clear_completed_rows();
+                    self.start_entry();
+                }
+            }
+            Phase::Entry(ticks) => {
+                self.charge_das();
+                if ticks > 1 {
+                    self.phase = Phase::Entry(ticks - 1);
+                } else {
+                    self.spawn_next();
+                }
+            }
+        }
+    }
+
+    fn advance_piece(&mut self) {
+        self.track_lowest_row();
+
+        let piece = self.piece.as_ref().unwrap();
+        let grounded = piece.collides_on_next(&self.board);
+        let mut put_next_block = false;
+
+        if grounded {
+            trace!("Lock timer = {}", self.lock_timer);
+            if self.lock_timer >= self.params.lock_delay || self.lock_resets_exhausted() {
+                trace!("Reached limit of {}", self.params.lock_delay);
+                self.lock_piece();
+                if self.piece.is_none() {
+                    return;
+                }
+                put_next_block = true;
+            } else {
+                trace!("Limit not reached, increasing lock timer");
+                self.lock_timer += 1;
+            }
+        } else if self.params.lock_down != LockDownMode::Step {
+            trace!("Not grounded, resetting lock timer");
+            self.lock_timer = 0;
+        }
+
+        if self.apply_side_move() && grounded {
+            self.reset_lock_timer();
+        }
+
+        if !put_next_block && !grounded {
+            trace!("Applying gravity...");
+            self.apply_gravity();
+        }
+    }
+
+    fn apply_side_move(&mut self) -> bool {
+        let sign: i32 = match self.side_move_to_perform {
+            Some(SideMoves::Left) => -1,
+            Some(SideMoves::Right) => 1,
+            _ => 0,
+        };
+
+        if sign == 0 {
+            self.side_movement_accumulator = 0.0;
+            return false;
+        }
+
+        if !self.side_tapped {
+            self.side_tapped = true;
+            return self.side_move_signed(sign);
+        }
+
+        let tick_ms = self.tick_ms();
+        self.das_charge += tick_ms;
+        if self.das_charge < self.params.das {
+            return false;
+        }
+        if self.das_cut > 0.0 {
+            self.das_cut -= tick_ms;
+            return false;
+        }
+
+        let mut moved = false;
+        if self.params.arr == 0.0 {
+            while self.side_move_signed(sign) {
+                moved = true;
+            }
+            return moved;
+        }
+
+        self.side_movement_accumulator += tick_ms / self.params.arr;
+        while self.side_movement_accumulator >= 1.0 {
+            if self.side_move_signed(sign) {
+                moved = true;
+            }
+            self.side_movement_accumulator -= 1.0;
+        }
+
+        moved
+    }
+
+    /// Forgets the DAS charged with the piece in play, unless it is preserved.
+    fn discard_das(&mut self) {
+        if !self.params.preserve_das {
+            self.das_charge = 0.0;
+        }
+    }
+
+    /// Keeps charging DAS while there is no piece to move.
+    fn charge_das(&mut self) {
+        if self.side_move_to_perform.is_some() {
+            self.das_charge += self.tick_ms();
+        }
+    }
+
+    fn tick_ms(&self) -> f64 {
+        1000.0 / self.params.tick_rate as f64
+    }
+
+    /// Holds back a charged DAS for `dcd` milliseconds, so that a piece does not
+    /// slide away right after a rotation or a spawn.
+    fn cut_das(&mut self) {
+        if self.side_tapped && self.das_charge >= self.params.das {
+            self.das_cut = self.params.dcd;
+            self.side_movement_accumulator = 0.0;
+        }
+    }
+
+    fn side_move_signed<T: Into<f64>>(&mut self, sign: T) -> bool {
+        if sign.into() > 0.0 {
+            self.move_right()
+        } else {
+            self.move_left()
+        }
+    }
+
+    fn apply_gravity(&mut self) {
+        if self.soft_dropping && self.params.soft_drop_factor.is_infinite() {
+            self.sonic_drop();
+            return;
+        }
+
+        self.down_movement_accumulator += self.current_gravity;
+
+        if self.down_movement_accumulator >= 1.0 {
+            let piece = self.piece.as_mut().unwrap();
+
+            while self.down_movement_accumulator >= 1.0 {
+                if !piece.collides_on_next(&self.board) {
+                    piece.move_down();
+                    self.last_move = Moves::Fall;
+                    if self.soft_dropping {
+                        self.scoring.soft_drop(1);
+                    }
+                }
+                self.down_movement_accumulator -= 1.0;
+            }
+        }
+    }
+
+    fn base_gravity(&self) -> f64 {
+        match self.params.gravity_curve {
+            GravityCurve::Fixed => self.params.gravity,
+            GravityCurve::Guideline => {
+                guideline_gravity(self.scoring.level(), self.params.tick_rate)
+            }
+        }
+    }
+
+    fn update_gravity(&mut self) {
+        self.current_gravity = if self.soft_dropping {
+            self.base_gravity() * self.params.soft_drop_factor
+        } else {
+            self.base_gravity()
+        };
+    }
+
+    fn reset_drop(&mut self) {
+        self.soft_dropping = false;
+        self.update_gravity();
+    }
+
+    fn move_left(&mut self) -> bool {
+        let piece = self.piece.as_mut().unwrap();
+        if piece.try_move_left(&self.board) {
+            self.last_move = Moves::Side;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn move_right(&mut self) -> bool {
+        let piece = self.piece.as_mut().unwrap();
+        if piece.try_move_right(&self.board) {
+            self.last_move = Moves::Side;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Direction to move to according to the SOCD policy when both side keys are held.
+    fn resolve_side(&self) -> Option<SideMoves> {
+        match (self.held_sides.as_slice(), self.params.socd) {
+            ([], _) => None,
+            ([only], _) => Some(*only),
+            (_, SocdPolicy::Neutral) => None,
+            (held, SocdPolicy::LastInput) => held.last().copied(),
+            (held, SocdPolicy::FirstInput) => held.first().copied(),
+        }
+    }
+
+    fn press_side(&mut self, side: SideMoves) {
+        // repeated presses from the OS key repeat
+        if self.held_sides.contains(&side) {
+            return;
+        }
+        self.held_sides.push(side);
+
+        let resolved = self.resolve_side();
+        if resolved == Some(side) {
+            self.reset_side_key_pressed();
+            self.side_move_to_perform = Some(side);
+        } else if resolved.is_none() {
+            self.reset_side_key_pressed();
+        }
+    }
+
+    fn release_side(&mut self, side: SideMoves) {
+        self.held_sides.retain(|&s| s != side);
+
+        let resolved = self.resolve_side();
+        if resolved != self.side_move_to_perform {
+            self.reset_side_key_pressed();
+            // the direction still held resumes with a new DAS charge, its tap already happened
+            if resolved.is_some() {
+                self.side_move_to_perform = resolved;
+                self.side_tapped = true;
+            }
+        }
+    }
+
+    fn reset_side_key_pressed(&mut self) {
+        self.side_move_to_perform = None;
+        self.das_charge = 0.0;
+        self.side_tapped = false;
+        self.das_cut = 0.0;
+    }
+
+    fn piece_bottom(&self) -> isize {
+        let piece = self.piece.as_ref().unwrap();
+        let cells = piece.tetris_piece_ref().set_cells();
+        piece.row() + cells.map(|(i, _)| i).max().unwrap_or(0)
+    }
+
+    fn track_lowest_row(&mut self) {
+        let bottom = self.piece_bottom();
+        if bottom > self.lowest_row {
+            self.lowest_row = bottom;
+            self.lock_resets = 0;
+            self.lock_timer = 0;
+        }
+    }
+
+    fn lock_resets_exhausted(&self) -> bool {
+        self.params.lock_down == LockDownMode::Extended && self.lock_resets >= MAX_LOCK_RESETS
+    }
+
+    /// Called when the piece moved or rotated while on the ground.
+    fn reset_lock_timer(&mut self) {
+        match self.params.lock_down {
+            LockDownMode::Extended => {
+                self.lock_resets += 1;
+                if !self.lock_resets_exhausted() {
+                    trace!("Lock reset {}, resetting lock timer", self.lock_resets);
+                    self.lock_timer = 0;
+                }
+            }
+            LockDownMode::Infinite => self.lock_timer = 0,
+            LockDownMode::Step => {}
+        }
+    }
+
+    fn rotate<F: Fn(&mut TetrisPiece)>(&mut self, rotate_piece: F) {
+        let piece_with_pos = self.piece.as_mut().unwrap();
+        let prev_rot = piece_with_pos.tetris_piece_ref().rotation;
+
+        rotate_piece(piece_with_pos.tetris_piece_mut());
+
+        if let Some(kick) = piece_with_pos.can_rotate(prev_rot, &self.board) {
+            piece_with_pos.kick_by(kick.offset);
+            self.last_move = Moves::Rotate;
+            self.last_kick = Some(kick).filter(|k| is_not_empty(k.offset));
+            self.cut_das();
+            if self.piece.as_ref().unwrap().collides_on_next(&self.board) {
+                self.reset_lock_timer();
+            }
+        } else {
+            piece_with_pos.tetris_piece_mut().set_rotation(prev_rot);
+        }
+    }
+
+    fn hard_drop(&mut self) {
+        let piece = self.piece.as_mut().unwrap();
+        let mut cells = 0;
+
+        while !piece.collides_on_next(&self.board) {
+            piece.move_down();
+            cells += 1;
+        }
+
+        self.scoring.hard_drop(cells);
+        self.lock_piece();
+    }
+
+    fn hold(&mut self) {
+        if HoldTetrisPiece::can_swap(&self.hold_piece) {
+            self.discard_das();
+            let p = self.piece.take();
+            let hp = self.hold_piece.take();
+
+            self.hold_piece = Some(HoldTetrisPiece::new(p.unwrap().tetris_piece()));
+            if let Some(hp) = hp {
+                self.spawn(hp.piece);
+            }
+
+            if self.piece.is_none() {
+                self.next_block(None);
+            }
+
+            self.hold_piece.as_mut().unwrap().set_hold();
+        }
+    }
+
+    fn start_soft_drop(&mut self) {
+        self.soft_dropping = true;
+        self.update_gravity();
+    }
+
+    /// Moves the piece to the floor without locking it. `last_move` only changes
+    /// when the piece moved, so a rotation on the floor still counts as a spin.
+    fn sonic_drop(&mut self) {
+        let piece = self.piece.as_mut().unwrap();
+        let mut cells = 0;
+
+        while !piece.collides_on_next(&self.board) {
+            piece.move_down();
+            cells += 1;
+        }
+
+        if cells > 0 {
+            self.last_move = Moves::Fall;
+            self.scoring.soft_drop(cells);
+        }
+        self.down_movement_accumulator = 0.0;
+    }
+
+    pub fn remove_line(&mut self, line: usize) {
+        self.record(ReplayAction::RemoveLine(line));
+        let row = self.buffer_rows() + line as isize;
+        if row < self.board.rows {
+            self.board.remove_row(row)
+        }
+    }
+
+    pub fn set_current(&mut self, p: PlayableTetrisPieceType) {
+        self.record(ReplayAction::SetCurrent(p));
+        if let Phase::LineClear(_) = self.phase {
+            self.clear_completed_rows();
+        }
+        self.phase = Phase::Falling;
+        self.discard_das();
+        self.next_block(Some(p));
+    }
+
+    fn new_block_in_buffer(&mut self) {
+        let piece = self.randomizer.next_piece();
+        let piece = self.new_piece(piece);
+        self.buffer_next_pieces.push_front(piece);
+    }
+
+    fn fill_buffer(&mut self) {
+        for _ in 0..5 {
+            self.new_block_in_buffer()
+        }
+    }
+
+    fn new_piece(&self, piece: PlayableTetrisPieceType) -> TetrisPiece {
+        TetrisPiece::with_rotation_system(piece, self.rotation_system)
+            .with_kicks_180(self.params.kicks_180)
+    }
+
+    fn build_piece_with_pos(&self, piece: TetrisPiece) -> TetrisPieceWithPosition {
+        let (row, col) = self.spawn_position();
+        TetrisPieceWithPosition::new(row, col, piece)
+    }
+
+    fn save_placement(&mut self) {
+        if self.history.is_none() {
+            return;
+        }
+
+        let snapshot = GameSnapshot {
+            board: self.board.clone(),
+            piece: self.piece.clone(),
+            hold_piece: self.hold_piece.clone(),
+            next_pieces: self.buffer_next_pieces.clone(),
+            randomizer: self.randomizer.clone(),
+            b2b_chain: self.b2b_chain,
+            current_combo: self.current_combo,
+            scoring: self.scoring.clone(),
+            last_score: self.last_score,
+            garbage: self.garbage.clone(),
+            total_attack: self.total_attack,
+            placed_pieces: self.placed_pieces,
+            game_over: self.game_over,
+        };
+        self.history.as_mut().unwrap().push(snapshot);
+    }
+
+    fn restore(&mut self, snapshot: GameSnapshot) {
+        self.board = snapshot.board;
+        self.piece = snapshot.piece;
+        self.hold_piece = snapshot.hold_piece;
+        self.buffer_next_pieces = snapshot.next_pieces;
+        self.randomizer = snapshot.randomizer;
+        self.b2b_chain = snapshot.b2b_chain;
+        self.current_combo = snapshot.current_combo;
+        self.scoring = snapshot.scoring;
+        self.last_score = snapshot.last_score;
+        self.garbage = snapshot.garbage;
+        self.total_attack = snapshot.total_attack;
+        self.placed_pieces = snapshot.placed_pieces;
+        self.game_over = snapshot.game_over;
+
+        self.last_attack = 0;
+        self.outgoing_garbage = 0;
+        self.last_move = Moves::Fall;
+        self.last_kick = None;
+        self.phase = Phase::Falling;
+        self.buffered_keys.clear();
+        self.held_sides.clear();
+        self.held_rotation = None;
+        self.hold_held = false;
+        self.lock_timer = 0;
+        self.lock_resets = 0;
+        self.lowest_row = self.piece_bottom();
+        self.down_movement_accumulator = 0.0;
+        self.side_movement_accumulator = 0.0;
+        self.reset_side_key_pressed();
+        self.reset_drop();
+    }
+
+    fn spawn(&mut self, piece: TetrisPiece) {
+        self.piece = Some(self.build_piece_with_pos(piece));
+
+        // the rotation can kick the piece out of an obstructed spawn
+        if let Some(key) = self.held_rotation.filter(|_| self.params.irs) {
+            self.press_piece_key(key);
+        }
+        if self.piece.as_ref().unwrap().collides(&self.board) {
+            self.game_over.get_or_insert(GameOverReason::BlockOut);
+        }
+
+        self.last_move = Moves::Fall;
+        self.last_kick = None;
+        self.lock_timer = 0;
+        self.lock_resets = 0;
+        self.lowest_row = self.piece_bottom();
+        self.cut_das();
+    }
+
+    fn next_block(&mut self, force_piece: Option<PlayableTetrisPieceType>) {
+        let piece = match force_piece {
+            None => self.buffer_next_pieces.pop_back().unwrap(),
+            Some(p) => self.new_piece(p),
+        };
+        self.spawn(piece);
+        self.new_block_in_buffer();
+        self.reset_drop();
+        if let Some(hold_piece) = self.hold_piece.as_mut() {
+            hold_piece.reset_hold();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_structs::GameMode;
+    use crate::enums::TetrisPieceRotation;
+    use crate::garbage::GarbageParams;
+    use crate::rotation::RotationSystemType;
+
+    fn tap(game: &mut Game, key: GameKey) -> TetrisUpdateResult {
+        game.tick(&[GameInput::Pressed(key)]);
+        game.tick(&[GameInput::Released(key)])
+    }
+
+    fn started_game(rows: &[&str], pieces: &[PlayableTetrisPieceType]) -> Game {
+        let mut game = Game::new(GameParams::default());
+        game.start_with(rows, pieces);
+        game
+    }
+
+    #[test]
+    fn test_hard_drop_locks_piece() {
+        let mut game = started_game(&[], &[PlayableTetrisPieceType::O]);
+
+        let result = tap(&mut game, GameKey::HardDrop);
+
+        assert_eq!(result, TetrisUpdateResult::Continue);
+        let (r, c) = (game.board().rows, game.board().cols);
+        assert!(game.board().is_set(r - 1, c / 2));
+        assert!(game.board().is_set(r - 2, c / 2));
+        assert!(game.board().is_set(r - 1, c / 2 + 1));
+        assert!(game.board().is_set(r - 2, c / 2 + 1));
+    }
+
+    #[test]
+    fn test_hard_drop_clears_tetris() {
+        let rows = ["********* ", "********* ", "********* ", "********* "];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::I]);
+
+        tap(&mut game, GameKey::NextRotation);
+        for _ in 0..3 {
+            tap(&mut game, GameKey::Right);
+        }
+        tap(&mut game, GameKey::HardDrop);
+
+        assert_eq!(game.last_score(), Some(ScoreType::AllClear));
+        assert!(game.board().is_empty());
+    }
+
+    #[test]
+    fn test_hard_drop_clears_single() {
+        let rows = ["*****  ***"];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::O]);
+
+        tap(&mut game, GameKey::HardDrop);
+
+        assert_eq!(game.last_score(), Some(ScoreType::Single));
+        assert!(!game.board().is_empty());
+    }
+
+    #[test]
+    fn test_scoring_counts_drops_lines_and_perfect_clear() {
+        let mut game = started_game(&[], &[PlayableTetrisPieceType::O]);
+        tap(&mut game, GameKey::HardDrop);
+        assert_eq!(game.score(), 2 * 20);
+
+        let rows = ["********* ", "********* ", "********* ", "********* "];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::I]);
+        tap(&mut game, GameKey::NextRotation);
+        for _ in 0..3 {
+            tap(&mut game, GameKey::Right);
+        }
+        tap(&mut game, GameKey::HardDrop);
+
+        assert_eq!(game.lines(), 4);
+        assert_eq!(game.score(), 800 + 2000 + 2 * 18);
+    }
+
+    #[test]
+    fn test_garbage_rises_when_piece_locks_without_clearing() {
+        let params = GameParams {
+            garbage: GarbageParams {
+                delay: 0,
+                ..GarbageParams::default()
+            },
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&[], &[PlayableTetrisPieceType::O]);
+        game.receive_garbage(3);
+
+        tap(&mut game, GameKey::HardDrop);
+
+        let rows = game.board().rows;
+        assert_eq!(game.pending_garbage(), 0);
+        for i in rows - 3..rows {
+            assert_eq!(game.board().row_bits(i).count_ones(), 9);
+        }
+        assert!(game.board().is_set(rows - 4, 5));
+    }
+
+    #[test]
+    fn test_line_clear_cancels_garbage() {
+        let rows = ["********  ", "********  ", "**** *****"];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::O]);
+        game.receive_garbage(3);
+
+        for _ in 0..4 {
+            tap(&mut game, GameKey::Right);
+        }
+        tap(&mut game, GameKey::HardDrop);
+
+        assert_eq!(game.last_attack(), 1);
+        assert_eq!(game.pending_garbage(), 2);
+        assert_eq!(game.take_outgoing_garbage(), 0);
+    }
+
+    #[test]
+    fn test_attack_left_after_cancelling_is_sent() {
+        let rows = ["********* "; 4];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::I]);
+        game.receive_garbage(1);
+
+        tap(&mut game, GameKey::NextRotation);
+        for _ in 0..3 {
+            tap(&mut game, GameKey::Right);
+        }
+        tap(&mut game, GameKey::HardDrop);
+
+        // tetris plus perfect clear
+        assert_eq!(game.last_attack(), 14);
+        assert_eq!(game.total_attack(), 14);
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.take_outgoing_garbage(), 13);
+        assert_eq!(game.take_outgoing_garbage(), 0);
+        assert!(game.attack_per_minute() > 0.0);
+    }
+
+    #[test]
+    fn test_top_out_when_garbage_pushes_stack_past_ceiling() {
+        let params = GameParams {
+            garbage: GarbageParams {
+                delay: 0,
+                ..GarbageParams::default()
+            },
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&["********* "; 10], &[PlayableTetrisPieceType::O]);
+        game.receive_garbage(30);
+
+        assert_eq!(
+            tap(&mut game, GameKey::HardDrop),
+            TetrisUpdateResult::GameOver {
+                reason: GameOverReason::TopOut
+            }
+        );
+    }
+
+    #[test]
+    fn test_undo_and_redo_placements() {
+        let rows = ["********  "];
+        let mut game = started_game(
+            &rows,
+            &[PlayableTetrisPieceType::O, PlayableTetrisPieceType::T],
+        );
+        let rows = game.board().rows;
+        assert!(!game.can_undo());
+
+        for _ in 0..4 {
+            tap(&mut game, GameKey::Right);
+        }
+        tap(&mut game, GameKey::HardDrop);
+        let next = game.next_pieces().next().unwrap().piece_type;
+        assert_eq!(game.lines(), 1);
+
+        assert!(game.undo());
+        assert!(!game.can_undo());
+        assert_eq!(game.lines(), 0);
+        assert_eq!(game.board().row_bits(rows - 1).count_ones(), 8);
+        assert_eq!(
+            game.piece().unwrap().tetris_piece_ref().piece_type,
+            PlayableTetrisPieceType::O
+        );
+
+        assert!(game.redo());
+        assert!(!game.can_redo());
+        assert_eq!(game.lines(), 1);
+        assert_eq!(
+            game.piece().unwrap().tetris_piece_ref().piece_type,
+            PlayableTetrisPieceType::T
+        );
+        assert_eq!(game.next_pieces().next().unwrap().piece_type, next);
+    }
+
+    #[test]
+    fn test_new_placement_drops_redo() {
+        let mut game = started_game(&[], &[]);
+        tap(&mut game, GameKey::HardDrop);
+        tap(&mut game, GameKey::HardDrop);
+
+        assert!(game.undo());
+        tap(&mut game, GameKey::HardDrop);
+
+        assert!(!game.can_redo());
+        assert!(!game.redo());
+        assert!(game.can_undo());
+    }
+
+    #[test]
+    fn test_undo_disabled_in_ranked() {
+        let params = GameParams {
+            mode: GameMode::Ranked,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start();
+        tap(&mut game, GameKey::HardDrop);
+
+        assert!(!game.can_undo());
+        assert!(!game.undo());
+    }
+
+    fn handling(das: f64, arr: f64, dcd: f64, preserve_das: bool) -> Game {
+        let params = GameParams {
+            das,
+            arr,
+            dcd,
+            preserve_das,
+            gravity_curve: GravityCurve::Fixed,
+            gravity: 0.0001,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&[], &[PlayableTetrisPieceType::O; 3]);
+        game
+    }
+
+    fn col(game: &Game) -> isize {
+        game.piece().unwrap().col()
+    }
+
+    #[test]
+    fn test_das_and_arr_in_milliseconds() {
+        // at 60 ticks per second DAS charges on the third tick, then ARR moves every other tick
+        let mut game = handling(45.0, 33.0, 0.0, true);
+        let start = col(&game);
+
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        assert_eq!(col(&game), start + 1);
+        for _ in 0..3 {
+            game.tick(&[]);
+        }
+        assert_eq!(col(&game), start + 1);
+        game.tick(&[]);
+        assert_eq!(col(&game), start + 2);
+    }
+
+    #[test]
+    fn test_zero_arr_moves_to_the_wall() {
+        let mut game = handling(45.0, 0.0, 0.0, true);
+
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        for _ in 0..3 {
+            game.tick(&[]);
+        }
+
+        let mut piece = game.piece().unwrap().clone();
+        assert!(!piece.try_move_right(game.board()));
+    }
+
+    fn charge_right_and_drop(game: &mut Game) -> isize {
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        for _ in 0..3 {
+            game.tick(&[]);
+        }
+        game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+        game.board().cols / 2 - 1
+    }
+
+    #[test]
+    fn test_das_charge_kept_into_next_piece() {
+        for &preserve_das in &[true, false] {
+            let mut game = handling(45.0, 0.0, 0.0, preserve_das);
+            let spawned = charge_right_and_drop(&mut game);
+
+            assert_eq!(col(&game) > spawned, preserve_das);
+        }
+    }
+
+    #[test]
+    fn test_das_charged_during_are() {
+        // 50 ms are charged until the spawn, the charge of the previous piece is not kept
+        for &(das, shifted) in &[(45.0, true), (70.0, false)] {
+            let params = GameParams {
+                das,
+                arr: 0.0,
+                dcd: 0.0,
+                preserve_das: false,
+                are: 3,
+                gravity_curve: GravityCurve::Fixed,
+                gravity: 0.0001,
+                ..GameParams::default()
+            };
+            let mut game = Game::new(params);
+            game.start_with(&[], &[PlayableTetrisPieceType::O; 3]);
+            let spawned = charge_right_and_drop(&mut game);
+
+            for _ in 0..2 {
+                game.tick(&[]);
+            }
+            assert_eq!(col(&game), spawned);
+            game.tick(&[]);
+            assert_eq!(col(&game) > spawned, shifted);
+            game.tick(&[]);
+            assert!(col(&game) > spawned);
+        }
+    }
+
+    #[test]
+    fn test_dcd_holds_back_charged_das_after_spawn() {
+        let mut game = handling(45.0, 0.0, 45.0, true);
+        let spawned = charge_right_and_drop(&mut game);
+
+        for _ in 0..2 {
+            assert_eq!(col(&game), spawned);
+            game.tick(&[]);
+        }
+        assert_eq!(col(&game), spawned);
+        game.tick(&[]);
+        assert!(col(&game) > spawned);
+    }
+
+    fn socd_game(socd: SocdPolicy) -> Game {
+        let params = GameParams {
+            socd,
+            das: 45.0,
+            arr: 0.0,
+            gravity_curve: GravityCurve::Fixed,
+            gravity: 0.0001,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&[], &[PlayableTetrisPieceType::O]);
+        game
+    }
+
+    #[test]
+    fn test_released_key_resumes_held_direction() {
+        let mut game = socd_game(SocdPolicy::LastInput);
+        let start = col(&game);
+
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        game.tick(&[GameInput::Pressed(GameKey::Left)]);
+        assert_eq!(col(&game), start);
+
+        game.tick(&[GameInput::Released(GameKey::Left)]);
+        assert_eq!(col(&game), start);
+        for _ in 0..3 {
+            game.tick(&[]);
+        }
+        let mut piece = game.piece().unwrap().clone();
+        assert!(!piece.try_move_right(game.board()));
+    }
+
+    #[test]
+    fn test_socd_neutral_and_first_input() {
+        let mut game = socd_game(SocdPolicy::Neutral);
+        let start = col(&game);
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        game.tick(&[GameInput::Pressed(GameKey::Left)]);
+        for _ in 0..5 {
+            game.tick(&[]);
+        }
+        assert_eq!(col(&game), start + 1);
+
+        let mut game = socd_game(SocdPolicy::FirstInput);
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        game.tick(&[GameInput::Pressed(GameKey::Left)]);
+        assert_eq!(col(&game), start + 1);
+        for _ in 0..3 {
+            game.tick(&[]);
+        }
+        assert!(col(&game) > start + 1);
+    }
+
+    #[test]
+    fn test_key_repeat_does_not_tap_again() {
+        let mut game = socd_game(SocdPolicy::LastInput);
+        let start = col(&game);
+
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+
+        assert_eq!(col(&game), start + 1);
+    }
+
+    fn landed_game(lock_down: LockDownMode) -> Game {
+        let params = GameParams {
+            lock_down,
+            lock_delay: 20,
+            gravity_curve: GravityCurve::Fixed,
+            gravity: 0.0001,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(
+            &[],
+            &[PlayableTetrisPieceType::O, PlayableTetrisPieceType::O],
+        );
+        tap(&mut game, GameKey::SonicDrop);
+        game
+    }
+
+    fn shuffle_on_floor(game: &mut Game, moves: usize) {
+        for i in 0..moves {
+            tap(
+                game,
+                if i % 2 == 0 {
+                    GameKey::Left
+                } else {
+                    GameKey::Right
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_extended_lock_down_caps_resets() {
+        let mut game = landed_game(LockDownMode::Extended);
+
+        shuffle_on_floor(&mut game, 14);
+        assert!(game.board().is_empty());
+
+        shuffle_on_floor(&mut game, 1);
+        assert!(!game.board().is_empty());
+    }
+
+    #[test]
+    fn test_infinite_lock_down_never_runs_out() {
+        let mut game = landed_game(LockDownMode::Infinite);
+
+        shuffle_on_floor(&mut game, 50);
+
+        assert!(game.board().is_empty());
+    }
+
+    #[test]
+    fn test_step_lock_down_ignores_moves() {
+        let mut game = landed_game(LockDownMode::Step);
+
+        shuffle_on_floor(&mut game, 10);
+
+        assert!(!game.board().is_empty());
+    }
+
+    #[test]
+    fn test_are_delays_spawn_and_buffers_rotation() {
+        let params = GameParams {
+            are: 3,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(
+            &[],
+            &[PlayableTetrisPieceType::O, PlayableTetrisPieceType::T],
+        );
+
+        game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+        assert!(game.piece().is_none());
+        game.tick(&[
+            GameInput::Released(GameKey::HardDrop),
+            GameInput::Pressed(GameKey::NextRotation),
+        ]);
+        assert!(game.piece().is_none());
+        game.tick(&[GameInput::Released(GameKey::NextRotation)]);
+
+        let piece = game.piece().unwrap().tetris_piece_ref();
+        assert_eq!(piece.piece_type, PlayableTetrisPieceType::T);
+        assert_eq!(piece.rotation, TetrisPieceRotation::RIGHT);
+    }
+
+    #[test]
+    fn test_line_clear_delay_keeps_rows_until_it_ends() {
+        let params = GameParams {
+            line_clear_delay: 4,
+            ..GameParams::default()
+        };
+        let rows = ["********* ", "********* ", "********* ", "********* "];
+        let mut game = Game::new(params);
+        game.start_with(&rows, &[PlayableTetrisPieceType::I]);
+
+        tap(&mut game, GameKey::NextRotation);
+        for _ in 0..3 {
+            tap(&mut game, GameKey::Right);
+        }
+        game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+
+        assert_eq!(game.last_score(), Some(ScoreType::AllClear));
+        assert_eq!(game.clearing_rows().len(), 4);
+        game.tick(&[]);
+        game.tick(&[]);
+        assert!(!game.board().is_empty());
+
+        game.tick(&[]);
+        assert!(game.board().is_empty());
+        assert!(game.clearing_rows().is_empty());
+        assert!(game.piece().is_some());
+    }
+
+    fn irs_game(irs: bool, are: u32) -> Game {
+        let params = GameParams {
+            irs,
+            are,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(
+            &[],
+            &[PlayableTetrisPieceType::O, PlayableTetrisPieceType::T],
+        );
+        game
+    }
+
+    fn rotation(game: &Game) -> TetrisPieceRotation {
+        game.piece().unwrap().tetris_piece_ref().rotation
+    }
+
+    #[test]
+    fn test_undo_forgets_held_keys() {
+        let mut game = irs_game(true, 0);
+        tap(&mut game, GameKey::HardDrop);
+        game.tick(&[
+            GameInput::Pressed(GameKey::Right),
+            GameInput::Pressed(GameKey::NextRotation),
+        ]);
+
+        assert!(game.undo());
+        let start = col(&game);
+        game.tick(&[GameInput::Pressed(GameKey::Right)]);
+        assert_eq!(col(&game), start + 1);
+
+        // the rotation key is not held anymore for the next spawn
+        tap(&mut game, GameKey::HardDrop);
+        assert_eq!(rotation(&game), TetrisPieceRotation::ZERO);
+    }
+
+    #[test]
+    fn test_initial_rotation() {
+        for irs in [false, true] {
+            let mut game = irs_game(irs, 0);
+            game.tick(&[GameInput::Pressed(GameKey::NextRotation)]);
+            game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+
+            let expected = if irs {
+                TetrisPieceRotation::RIGHT
+            } else {
+                TetrisPieceRotation::ZERO
+            };
+            assert_eq!(rotation(&game), expected);
+        }
+    }
+
+    #[test]
+    fn test_initial_rotation_during_are_rotates_once() {
+        for irs in [false, true] {
+            let mut game = irs_game(irs, 3);
+            game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+            game.tick(&[GameInput::Pressed(GameKey::NextRotation)]);
+            game.tick(&[]);
+
+            assert_eq!(rotation(&game), TetrisPieceRotation::RIGHT);
+        }
+    }
+
+    #[test]
+    fn test_initial_hold() {
+        let params = GameParams {
+            ihs: true,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(
+            &[],
+            &[
+                PlayableTetrisPieceType::O,
+                PlayableTetrisPieceType::T,
+                PlayableTetrisPieceType::I,
+            ],
+        );
+
+        game.tick(&[GameInput::Pressed(GameKey::Hold)]);
+        game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+
+        let piece = game.piece().unwrap().tetris_piece_ref();
+        assert_eq!(piece.piece_type, PlayableTetrisPieceType::O);
+        let hold = game.hold_piece().unwrap();
+        assert_eq!(hold.piece.piece_type, PlayableTetrisPieceType::I);
+    }
+
+    #[test]
+    fn test_start_level_sets_gravity() {
+        let params = GameParams {
+            start_level: 5,
+            ..GameParams::default()
+        };
+        let game = Game::new(params);
+
+        assert_eq!(game.level(), 5);
+        assert_eq!(game.current_gravity, guideline_gravity(5, 60));
+    }
+
+    #[test]
+    fn test_hold_swaps_once_per_piece() {
+        let mut game = started_game(
+            &[],
+            &[PlayableTetrisPieceType::T, PlayableTetrisPieceType::I],
+        );
+
+        tap(&mut game, GameKey::Hold);
+        assert_eq!(
+            game.hold_piece().unwrap().piece.piece_type,
+            PlayableTetrisPieceType::T
+        );
+        assert_eq!(
+            game.piece().unwrap().tetris_piece_ref().piece_type,
+            PlayableTetrisPieceType::I
+        );
+        assert!(!game.can_swap_hold());
+
+        tap(&mut game, GameKey::Hold);
+        assert_eq!(
+            game.piece().unwrap().tetris_piece_ref().piece_type,
+            PlayableTetrisPieceType::I
+        );
+    }
+
+    #[test]
+    fn test_seeded_games_deal_same_pieces() {
+        let params = GameParams {
+            seed: Some(1234),
+            ..GameParams::default()
+        };
+        let mut first = Game::new(params.clone());
+        let mut second = Game::new(params);
+        first.start();
+        second.start();
+
+        let types = |g: &Game| g.next_pieces().map(|p| p.piece_type).collect::<Vec<_>>();
+        assert_eq!(types(&first), types(&second));
+        assert_eq!(first.seed(), 1234);
+    }
+
+    #[test]
+    fn test_pieces_use_configured_rotation_system() {
+        let params = GameParams {
+            rotation_system: RotationSystemType::Ars,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&[], &[PlayableTetrisPieceType::T]);
+
+        let cells: Vec<_> = game
+            .piece()
+            .unwrap()
+            .tetris_piece_ref()
+            .set_cells()
+            .collect();
+        assert_eq!(cells, vec![(1, 0), (1, 1), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn test_rotate_180() {
+        let mut game = started_game(&[], &[PlayableTetrisPieceType::T]);
+
+        tap(&mut game, GameKey::Rotate180);
+
+        let piece = game.piece().unwrap().tetris_piece_ref();
+        assert_eq!(piece.rotation, TetrisPieceRotation::TWO);
+        assert_eq!(game.last_move, Moves::Rotate);
+    }
+
+    #[test]
+    fn test_infinite_soft_drop_reaches_floor_without_locking() {
+        let params = GameParams {
+            soft_drop_factor: f64::INFINITY,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&[], &[PlayableTetrisPieceType::O]);
+
+        game.tick(&[GameInput::Pressed(GameKey::SoftDrop)]);
+
+        let piece = game.piece().unwrap();
+        assert!(piece.collides_on_next(game.board()));
+        assert!(game.board().is_empty());
+        assert_eq!(game.score(), piece.row() as u64 - 18);
+    }
+
+    #[test]
+    fn test_spin_kept_when_dropping_on_the_floor() {
+        let mut game = started_game(&[], &[PlayableTetrisPieceType::T]);
+
+        tap(&mut game, GameKey::SonicDrop);
+        assert_eq!(game.last_move, Moves::Fall);
+        assert!(game.board().is_empty());
+
+        tap(&mut game, GameKey::NextRotation);
+        tap(&mut game, GameKey::SoftDrop);
+        tap(&mut game, GameKey::SonicDrop);
+
+        assert_eq!(game.last_move, Moves::Rotate);
+    }
+
+    #[test]
+    fn test_block_out_when_spawn_overlaps_stack() {
+        let rows = vec!["**********"; GameParams::default().visible_height + 2];
+        let mut game = started_game(&rows, &[]);
+
+        assert_eq!(
+            game.tick(&[]),
+            TetrisUpdateResult::GameOver {
+                reason: GameOverReason::BlockOut
+            }
+        );
+    }
+
+    #[test]
+    fn test_lock_out_when_piece_locks_above_field() {
+        let rows = vec!["**********"; GameParams::default().visible_height];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::O]);
+
+        assert_eq!(game.tick(&[]), TetrisUpdateResult::Continue);
+        assert_eq!(
+            tap(&mut game, GameKey::HardDrop),
+            TetrisUpdateResult::GameOver {
+                reason: GameOverReason::LockOut
+            }
+        );
+        assert_eq!(game.game_over(), Some(GameOverReason::LockOut));
+    }
+
+    #[test]
+    fn test_piece_partially_in_field_is_not_lock_out() {
+        let rows = vec!["**********"; GameParams::default().visible_height - 1];
+        let mut game = started_game(&rows, &[PlayableTetrisPieceType::O]);
+
+        assert_eq!(
+            tap(&mut game, GameKey::HardDrop),
+            TetrisUpdateResult::Continue
+        );
+    }
+
+    #[test]
+    fn test_custom_dimensions_spawn_in_buffer() {
+        let params = GameParams {
+            width: 6,
+            visible_height: 8,
+            buffer_height: 4,
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start_with(&[], &[PlayableTetrisPieceType::T]);
+
+        assert_eq!(game.board().rows, 12);
+        assert_eq!(game.board().cols, 6);
+
+        let piece = game.piece().unwrap();
+        assert_eq!(piece.row(), 2);
+        assert_eq!(piece.col(), 2);
+        assert!(piece
+            .tetris_piece_ref()
+            .set_cells()
+            .all(|(i, _)| piece.row() + i < game.buffer_rows()));
+    }
+}
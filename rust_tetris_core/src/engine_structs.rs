@@ -0,0 +1,360 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    attack::AttackTableType,
+    board::TetrisBoard,
+    constants::{Kick, RotationKick},
+    enums::{PlayableTetrisPieceType, TetrisPieceRotation, TetrisPieceType},
+    garbage::GarbageParams,
+    pieces::TetrisPiece,
+    randomizer::RandomizerType,
+    rotation::{Kicks180Type, RotationSystemType},
+    scoring::{GravityCurve, DEFAULT_TICK_RATE},
+    spin::SpinRule,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameParams {
+    pub gravity: f64,
+    // infinity moves the piece straight to the floor
+    pub soft_drop_factor: f64,
+    // milliseconds before a held side key starts to auto repeat
+    pub das: f64,
+    // milliseconds between auto repeated moves, 0 moves straight to the wall
+    pub arr: f64,
+    // milliseconds a charged DAS waits after a rotation or a spawn
+    #[serde(default)]
+    pub dcd: f64,
+    // keeps the DAS charge of a held side key into the next piece
+    #[serde(default = "default_preserve_das")]
+    pub preserve_das: bool,
+    #[serde(default)]
+    pub socd: SocdPolicy,
+    pub lock_delay: u32,
+    #[serde(default)]
+    pub lock_down: LockDownMode,
+    // initial rotation: a rotation key held when a piece spawns rotates it
+    #[serde(default)]
+    pub irs: bool,
+    // initial hold: the hold key held when a piece spawns swaps it
+    #[serde(default)]
+    pub ihs: bool,
+    // ticks between a lock and the next spawn (ARE)
+    #[serde(default)]
+    pub are: u32,
+    // ticks the completed rows stay on the board before being removed
+    #[serde(default)]
+    pub line_clear_delay: u32,
+    #[serde(default)]
+    pub randomizer: RandomizerType,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub rotation_system: RotationSystemType,
+    #[serde(default)]
+    pub kicks_180: Kicks180Type,
+    #[serde(default)]
+    pub gravity_curve: GravityCurve,
+    #[serde(default = "default_start_level")]
+    pub start_level: u32,
+    #[serde(default)]
+    pub spin_rule: SpinRule,
+    #[serde(default)]
+    pub garbage: GarbageParams,
+    #[serde(default)]
+    pub attack_table: AttackTableType,
+    #[serde(default)]
+    pub mode: GameMode,
+    // logic ticks per second, the unit of every duration counted in frames
+    #[serde(default = "default_tick_rate")]
+    pub tick_rate: u32,
+    #[serde(default = "default_width")]
+    pub width: usize,
+    #[serde(default = "default_visible_height")]
+    pub visible_height: usize,
+    #[serde(default = "default_buffer_height")]
+    pub buffer_height: usize,
+}
+
+fn default_preserve_das() -> bool {
+    true
+}
+
+fn default_start_level() -> u32 {
+    1
+}
+
+fn default_tick_rate() -> u32 {
+    DEFAULT_TICK_RATE
+}
+
+fn default_width() -> usize {
+    10
+}
+
+fn default_visible_height() -> usize {
+    20
+}
+
+fn default_buffer_height() -> usize {
+    20
+}
+
+/// What to do when left and right are held together.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SocdPolicy {
+    // the last pressed direction wins
+    #[default]
+    LastInput,
+    // the piece stops until one of the keys is released
+    Neutral,
+    // the first pressed direction wins
+    FirstInput,
+}
+
+/// How moving a piece on the ground delays its lock.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockDownMode {
+    // moves and rotations reset the lock delay up to 15 times, reaching a lower row gives them back
+    #[default]
+    Extended,
+    // moves and rotations always reset the lock delay
+    Infinite,
+    // only falling to a lower row resets the lock delay
+    Step,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameMode {
+    #[default]
+    Practice,
+    // practice with the debug keys always available
+    Sandbox,
+    // no undo nor debug keys
+    Ranked,
+}
+
+impl GameMode {
+    pub fn allows_undo(self) -> bool {
+        self != GameMode::Ranked
+    }
+}
+
+impl Default for GameParams {
+    fn default() -> Self {
+        GameParams {
+            gravity: 0.016,
+            soft_drop_factor: 40.0,
+            das: 167.0,
+            arr: 33.0,
+            dcd: 0.0,
+            preserve_das: default_preserve_das(),
+            socd: SocdPolicy::default(),
+            lock_delay: 30,
+            lock_down: LockDownMode::default(),
+            irs: false,
+            ihs: false,
+            are: 0,
+            line_clear_delay: 0,
+            randomizer: RandomizerType::default(),
+            seed: None,
+            rotation_system: RotationSystemType::default(),
+            kicks_180: Kicks180Type::default(),
+            gravity_curve: GravityCurve::default(),
+            start_level: default_start_level(),
+            spin_rule: SpinRule::default(),
+            garbage: GarbageParams::default(),
+            attack_table: AttackTableType::default(),
+            mode: GameMode::default(),
+            tick_rate: default_tick_rate(),
+            width: default_width(),
+            visible_height: default_visible_height(),
+            buffer_height: default_buffer_height(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameKey {
+    Left,
+    Right,
+    NextRotation,
+    PrevRotation,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+    SonicDrop,
+    Hold,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameInput {
+    Pressed(GameKey),
+    Released(GameKey),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameOverReason {
+    // the spawned piece overlaps the stack
+    BlockOut,
+    // the piece locked entirely inside the hidden buffer
+    LockOut,
+    // the stack was pushed past the top of the buffer
+    TopOut,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TetrisUpdateResult {
+    Continue,
+    GameOver { reason: GameOverReason },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreType {
+    TSpin,
+    TSpinSingle,
+    TSpinDouble,
+    TSpinTriple,
+    TSpinMini,
+    TSpinMiniSingle,
+    TSpinMiniDouble,
+    Spin(PlayableTetrisPieceType),
+    SpinSingle(PlayableTetrisPieceType),
+    SpinDouble(PlayableTetrisPieceType),
+    SpinTriple(PlayableTetrisPieceType),
+    SpinQuad(PlayableTetrisPieceType),
+    Tetris,
+    AllClear,
+    Single,
+    Double,
+    Triple,
+}
+
+#[derive(Clone)]
+pub struct TetrisPieceWithPosition {
+    r: isize,
+    c: isize,
+    piece: TetrisPiece,
+}
+
+impl TetrisPieceWithPosition {
+    pub fn new(r: isize, c: isize, piece: TetrisPiece) -> Self {
+        TetrisPieceWithPosition { r, c, piece }
+    }
+
+    pub fn row(&self) -> isize {
+        self.r
+    }
+
+    pub fn col(&self) -> isize {
+        self.c
+    }
+
+    pub fn tetris_piece(self) -> TetrisPiece {
+        self.piece
+    }
+
+    pub fn tetris_piece_ref(&self) -> &TetrisPiece {
+        &self.piece
+    }
+
+    pub fn tetris_piece_mut(&mut self) -> &mut TetrisPiece {
+        &mut self.piece
+    }
+
+    pub fn finalize_on(&self, board: &mut TetrisBoard) {
+        for (i, j) in self.piece.set_cells() {
+            board.set(
+                i + self.row(),
+                j + self.col(),
+                TetrisPieceType::Playable(self.piece.piece_type),
+            );
+        }
+    }
+
+    pub fn collides(&self, matrix: &TetrisBoard) -> bool {
+        self.piece.collides_at(self.r, self.c, matrix)
+    }
+
+    pub fn collides_on_next(&self, matrix: &TetrisBoard) -> bool {
+        self.piece.collides_on_next(self.r, self.c, matrix)
+    }
+
+    pub fn collides_on_next_with_row(&self, r: isize, matrix: &TetrisBoard) -> bool {
+        self.piece.collides_on_next(r, self.c, matrix)
+    }
+
+    pub fn try_move_left(&mut self, matrix: &TetrisBoard) -> bool {
+        let first_col = self.piece.board().get_first_set_col().unwrap();
+
+        if self.c + first_col > 0 && !self.piece.collides_left(self.r, self.c, matrix) {
+            self.move_left();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn try_move_right(&mut self, matrix: &TetrisBoard) -> bool {
+        let last_col = self.piece.board().get_last_set_col().unwrap();
+
+        if self.c + last_col < matrix.cols - 1 && !self.piece.collides_right(self.r, self.c, matrix)
+        {
+            self.move_right();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn kick_by(&mut self, kick: Kick) {
+        self.r -= kick.1;
+        self.c += kick.0;
+    }
+
+    pub fn can_rotate(
+        &self,
+        prev_rot: TetrisPieceRotation,
+        matrix: &TetrisBoard,
+    ) -> Option<RotationKick> {
+        self.piece.find_kick(prev_rot, self.r, self.c, matrix)
+    }
+
+    pub fn move_down(&mut self) {
+        self.r += 1;
+    }
+
+    pub fn move_left(&mut self) {
+        self.c -= 1;
+    }
+
+    pub fn move_right(&mut self) {
+        self.c += 1;
+    }
+}
+
+#[derive(Clone)]
+pub struct HoldTetrisPiece {
+    pub piece: TetrisPiece,
+    pub already_hold: bool,
+}
+
+impl HoldTetrisPiece {
+    pub fn new(mut piece: TetrisPiece) -> Self {
+        piece.set_rotation(TetrisPieceRotation::ZERO);
+        HoldTetrisPiece {
+            piece,
+            already_hold: false,
+        }
+    }
+
+    pub fn can_swap(obj: &Option<HoldTetrisPiece>) -> bool {
+        obj.is_none() || !obj.as_ref().unwrap().already_hold
+    }
+    pub fn set_hold(&mut self) {
+        self.already_hold = true;
+    }
+    pub fn reset_hold(&mut self) {
+        self.already_hold = false;
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::board::{not_playable_piece_to_cell, TetrisBoard, TetrisCell};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GarbageParams {
+    // frames a batch waits in the queue before it can be inserted
+    pub delay: u32,
+    // chance for each row after the first of a batch to move the hole
+    pub messiness: f64,
+    // fixed hole column, random for each batch if missing
+    pub hole_column: Option<usize>,
+}
+
+impl Default for GarbageParams {
+    fn default() -> Self {
+        GarbageParams {
+            delay: 20,
+            messiness: 0.0,
+            hole_column: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageBatch {
+    pub lines: u32,
+    pub frames_left: u32,
+}
+
+#[derive(Clone)]
+pub struct GarbageQueue {
+    params: GarbageParams,
+    batches: VecDeque<GarbageBatch>,
+    rng: Pcg32,
+}
+
+impl GarbageQueue {
+    pub fn new(params: GarbageParams, seed: u64) -> Self {
+        GarbageQueue {
+            params,
+            batches: VecDeque::new(),
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    pub fn batches(&self) -> impl Iterator<Item = &GarbageBatch> {
+        self.batches.iter()
+    }
+
+    pub fn pending(&self) -> u32 {
+        self.batches.iter().map(|b| b.lines).sum()
+    }
+
+    pub fn receive(&mut self, lines: u32) {
+        if lines > 0 {
+            self.batches.push_back(GarbageBatch {
+                lines,
+                frames_left: self.params.delay,
+            });
+        }
+    }
+
+    /// Cancels pending garbage oldest first, returns the lines left over.
+    pub fn cancel(&mut self, mut lines: u32) -> u32 {
+        while lines > 0 {
+            match self.batches.front_mut() {
+                Some(batch) if batch.lines > lines => {
+                    batch.lines -= lines;
+                    lines = 0;
+                }
+                Some(batch) => {
+                    lines -= batch.lines;
+                    self.batches.pop_front();
+                }
+                None => break,
+            }
+        }
+
+        lines
+    }
+
+    pub fn tick(&mut self) {
+        for batch in self.batches.iter_mut() {
+            batch.frames_left = batch.frames_left.saturating_sub(1);
+        }
+    }
+
+    /// Pushes every batch whose delay elapsed into `board`, returns true if
+    /// filled cells were pushed past the top.
+    pub fn insert_ready(&mut self, board: &mut TetrisBoard) -> bool {
+        let mut topped_out = false;
+
+        while let Some(batch) = self.batches.front() {
+            if batch.frames_left > 0 {
+                break;
+            }
+
+            let lines = batch.lines;
+            self.batches.pop_front();
+
+            let mut hole = self.next_hole(board.cols);
+            for i in 0..lines {
+                if i > 0 && self.rng.gen_bool(self.params.messiness.clamp(0.0, 1.0)) {
+                    hole = self.next_hole(board.cols);
+                }
+                topped_out |= board.push_row(&garbage_row(board.cols, hole));
+            }
+        }
+
+        topped_out
+    }
+
+    fn next_hole(&mut self, cols: isize) -> isize {
+        match self.params.hole_column {
+            Some(col) => (col as isize).min(cols - 1),
+            None => self.rng.gen_range(0, cols),
+        }
+    }
+}
+
+fn garbage_row(cols: isize, hole: isize) -> Vec<TetrisCell> {
+    (0..cols)
+        .map(|j| {
+            if j == hole {
+                TetrisCell::EmptyCell
+            } else {
+                not_playable_piece_to_cell()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::TetrisPieceType;
+
+    fn queue(delay: u32) -> GarbageQueue {
+        GarbageQueue::new(
+            GarbageParams {
+                delay,
+                ..GarbageParams::default()
+            },
+            0,
+        )
+    }
+
+    #[test]
+    fn test_cancel_consumes_oldest_batches() {
+        let mut queue = queue(0);
+        queue.receive(2);
+        queue.receive(3);
+
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.pending(), 2);
+        assert_eq!(queue.cancel(4), 2);
+        assert_eq!(queue.pending(), 0);
+    }
+
+    #[test]
+    fn test_insert_waits_for_delay() {
+        let mut queue = queue(2);
+        let mut board = TetrisBoard::new(6, 4);
+        queue.receive(2);
+
+        queue.tick();
+        assert!(!queue.insert_ready(&mut board));
+        assert!(board.is_empty());
+
+        queue.tick();
+        assert!(!queue.insert_ready(&mut board));
+        assert_eq!(queue.pending(), 0);
+        assert_eq!(board.row_bits(5).count_ones(), 3);
+        assert_eq!(board.row_bits(4), board.row_bits(5));
+    }
+
+    #[test]
+    fn test_fixed_hole_column() {
+        let mut queue = GarbageQueue::new(
+            GarbageParams {
+                delay: 0,
+                messiness: 1.0,
+                hole_column: Some(1),
+            },
+            0,
+        );
+        let mut board = TetrisBoard::new(6, 4);
+        queue.receive(3);
+        queue.insert_ready(&mut board);
+
+        for i in 3..6 {
+            assert!(!board.is_set(i, 1));
+            assert_eq!(
+                board.get(i, 0),
+                TetrisCell::FilledCell(TetrisPieceType::NotPlayable)
+            );
+        }
+    }
+
+    #[test]
+    fn test_insert_reports_top_out() {
+        let mut queue = queue(0);
+        let mut board = TetrisBoard::new(3, 4);
+        board.set(0, 0, TetrisPieceType::NotPlayable);
+        queue.receive(1);
+
+        assert!(queue.insert_ready(&mut board));
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+
+use crate::{
+    board::TetrisBoard,
+    engine_structs::{GameOverReason, HoldTetrisPiece, ScoreType, TetrisPieceWithPosition},
+    garbage::GarbageQueue,
+    pieces::TetrisPiece,
+    randomizer::Randomizer,
+    scoring::Scoring,
+};
+
+const MAX_UNDO: usize = 100;
+
+/// Game state at the spawn of a piece.
+#[derive(Clone)]
+pub(crate) struct GameSnapshot {
+    pub board: TetrisBoard,
+    pub piece: Option<TetrisPieceWithPosition>,
+    pub hold_piece: Option<HoldTetrisPiece>,
+    pub next_pieces: VecDeque<TetrisPiece>,
+    pub randomizer: Box<dyn Randomizer>,
+    pub b2b_chain: u32,
+    pub current_combo: u32,
+    pub scoring: Scoring,
+    pub last_score: Option<ScoreType>,
+    pub garbage: GarbageQueue,
+    pub total_attack: u32,
+    pub placed_pieces: u64,
+    pub game_over: Option<GameOverReason>,
+}
+
+/// Undo and redo stacks of placements, `current` being the start of the piece
+/// in play.
+#[derive(Default)]
+pub(crate) struct History {
+    undo: VecDeque<GameSnapshot>,
+    redo: Vec<GameSnapshot>,
+    current: Option<GameSnapshot>,
+}
+
+impl History {
+    /// Starts a new placement, which drops every redo.
+    pub fn push(&mut self, snapshot: GameSnapshot) {
+        if let Some(previous) = self.current.replace(snapshot) {
+            if self.undo.len() == MAX_UNDO {
+                self.undo.pop_front();
+            }
+            self.undo.push_back(previous);
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self) -> Option<&GameSnapshot> {
+        let snapshot = self.undo.pop_back()?;
+        if let Some(current) = self.current.replace(snapshot) {
+            self.redo.push(current);
+        }
+        self.current.as_ref()
+    }
+
+    pub fn redo(&mut self) -> Option<&GameSnapshot> {
+        let snapshot = self.redo.pop()?;
+        if let Some(current) = self.current.replace(snapshot) {
+            self.undo.push_back(current);
+        }
+        self.current.as_ref()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
@@ -1,6 +1,20 @@
 #[macro_use]
 pub mod macros;
+pub mod attack;
 pub mod board;
+pub mod bot;
 pub mod constants;
+pub mod engine;
+pub mod engine_structs;
 pub mod enums;
+pub mod garbage;
+mod history;
+pub mod movegen;
 pub mod pieces;
+pub mod planner;
+pub mod randomizer;
+pub mod replay;
+pub mod rotation;
+pub mod scoring;
+pub mod spin;
+pub mod tbp;
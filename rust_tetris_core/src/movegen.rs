@@ -0,0 +1,275 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    board::TetrisBoard,
+    constants::{is_not_empty, RotationKick},
+    engine_structs::TetrisPieceWithPosition,
+    enums::TetrisPieceRotation,
+    pieces::TetrisPiece,
+    planner::{Placement, PlannerOptions},
+    scoring::SpinType,
+    spin::{detect_spin, SpinRule},
+};
+
+const ROTATIONS: [TetrisPieceRotation; 4] = [
+    TetrisPieceRotation::ZERO,
+    TetrisPieceRotation::RIGHT,
+    TetrisPieceRotation::TWO,
+    TetrisPieceRotation::LEFT,
+];
+
+// room around the board for the rotation boxes, which can stick out of it
+const MARGIN: isize = 4;
+
+/// A resting position reachable from spawn.
+#[derive(Clone)]
+pub struct GeneratedMove {
+    pub piece: TetrisPieceWithPosition,
+    pub rotated_last: bool,
+    // like the engine, only kicks that moved the piece are kept
+    pub kick: Option<RotationKick>,
+}
+
+impl GeneratedMove {
+    pub fn placement(&self) -> Placement {
+        Placement::of(&self.piece)
+    }
+
+    pub fn spin(&self, board: &TetrisBoard, rule: SpinRule) -> SpinType {
+        if self.rotated_last {
+            detect_spin(&self.piece, board, self.kick, rule)
+        } else {
+            SpinType::None
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LastMove {
+    Shift,
+    Rotation(RotationKick),
+}
+
+struct Search<'a> {
+    board: &'a TetrisBoard,
+    // the piece in each rotation, indexed like ROTATIONS
+    pieces: Vec<TetrisPiece>,
+    width: isize,
+    reached: Vec<Option<LastMove>>,
+    queue: VecDeque<(isize, isize, usize)>,
+}
+
+impl<'a> Search<'a> {
+    fn index(&self, r: isize, c: isize, rot: usize) -> Option<usize> {
+        let (r, c) = (r + MARGIN, c + MARGIN);
+        if r < 0 || c < 0 || c >= self.width {
+            return None;
+        }
+        let index = ((r * self.width + c) * 4) as usize + rot;
+        if index < self.reached.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    fn fits(&self, r: isize, c: isize, rot: usize) -> bool {
+        !self.pieces[rot].collides_at(r, c, self.board)
+    }
+
+    fn visit(&mut self, r: isize, c: isize, rot: usize, last_move: LastMove) {
+        let index = match self.index(r, c, rot) {
+            Some(index) => index,
+            None => return,
+        };
+
+        match (self.reached[index], last_move) {
+            (None, _) => {
+                self.reached[index] = Some(last_move);
+                self.queue.push_back((r, c, rot));
+            }
+            // the same spot reached with a rotation can be a spin
+            (Some(LastMove::Shift), LastMove::Rotation(_)) => {
+                self.reached[index] = Some(last_move);
+            }
+            _ => {}
+        }
+    }
+
+    fn rotate(&mut self, r: isize, c: isize, from: usize, to: usize) {
+        let piece = &self.pieces[to];
+        if let Some(kick) = piece.find_kick(ROTATIONS[from], r, c, self.board) {
+            let (x, y) = kick.offset;
+            self.visit(r - y, c + x, to, LastMove::Rotation(kick));
+        }
+    }
+}
+
+/// Every resting position `piece` can reach on `board`, one per set of
+/// occupied cells. Placements reachable with a rotation as last move are
+/// reported as such, as they can be spins.
+pub fn generate_moves(
+    board: &TetrisBoard,
+    piece: &TetrisPieceWithPosition,
+    options: &PlannerOptions,
+) -> Vec<GeneratedMove> {
+    if piece.collides(board) {
+        return vec![];
+    }
+
+    let pieces = ROTATIONS
+        .iter()
+        .map(|&rotation| {
+            let mut rotated = piece.tetris_piece_ref().clone();
+            rotated.set_rotation(rotation);
+            rotated
+        })
+        .collect();
+    let width = board.cols + 2 * MARGIN;
+    let mut search = Search {
+        board,
+        pieces,
+        width,
+        reached: vec![None; ((board.rows + 2 * MARGIN) * width * 4) as usize],
+        queue: VecDeque::new(),
+    };
+
+    let start_rot = piece.tetris_piece_ref().rotation as usize;
+    search.visit(piece.row(), piece.col(), start_rot, LastMove::Shift);
+
+    let mut rotations = vec![1, 3];
+    if options.rotate_180 {
+        rotations.push(2);
+    }
+
+    let mut visited = vec![];
+    while let Some((r, c, rot)) = search.queue.pop_front() {
+        visited.push((r, c, rot));
+
+        for dc in [-1, 1] {
+            if search.fits(r, c + dc, rot) {
+                search.visit(r, c + dc, rot, LastMove::Shift);
+            }
+        }
+        if options.soft_drop && search.fits(r + 1, c, rot) {
+            search.visit(r + 1, c, rot, LastMove::Shift);
+        }
+        for &turn in &rotations {
+            search.rotate(r, c, rot, (rot + turn) % 4);
+        }
+    }
+
+    let mut moves: Vec<GeneratedMove> = vec![];
+    let mut by_cells: HashMap<Vec<(isize, isize)>, usize> = HashMap::new();
+
+    for (r, c, rot) in visited {
+        let mut row = r;
+        while !search.pieces[rot].collides_on_next(row, c, board) {
+            row += 1;
+        }
+        // with soft drops, the spot below is a state of its own
+        let last_move = if row == r {
+            search.reached[search.index(r, c, rot).unwrap()].unwrap()
+        } else if options.soft_drop {
+            continue;
+        } else {
+            LastMove::Shift
+        };
+
+        let (rotated_last, kick) = match last_move {
+            LastMove::Shift => (false, None),
+            LastMove::Rotation(kick) => (true, Some(kick).filter(|k| is_not_empty(k.offset))),
+        };
+        let generated = GeneratedMove {
+            piece: TetrisPieceWithPosition::new(row, c, search.pieces[rot].clone()),
+            rotated_last,
+            kick,
+        };
+
+        let mut cells: Vec<_> = generated
+            .piece
+            .tetris_piece_ref()
+            .set_cells()
+            .map(|(i, j)| (i + row, j + c))
+            .collect();
+        cells.sort_unstable();
+
+        match by_cells.get(&cells) {
+            Some(&index) => {
+                if generated.rotated_last && !moves[index].rotated_last {
+                    moves[index] = generated;
+                }
+            }
+            None => {
+                by_cells.insert(cells, moves.len());
+                moves.push(generated);
+            }
+        }
+    }
+
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{PlayableTetrisPieceType, TetrisPieceType};
+
+    fn board(rows: &[&str]) -> TetrisBoard {
+        let mut board = TetrisBoard::new(22, 10);
+        let offset = board.rows - rows.len() as isize;
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                if c == '*' {
+                    board.set(
+                        offset + i as isize,
+                        j as isize,
+                        TetrisPieceType::NotPlayable,
+                    );
+                }
+            }
+        }
+        board
+    }
+
+    fn count(piece: PlayableTetrisPieceType) -> usize {
+        let piece = TetrisPieceWithPosition::new(0, 4, TetrisPiece::new(piece));
+        generate_moves(&board(&[]), &piece, &PlannerOptions::default()).len()
+    }
+
+    #[test]
+    fn test_counts_on_empty_board() {
+        assert_eq!(count(PlayableTetrisPieceType::T), 34);
+        assert_eq!(count(PlayableTetrisPieceType::O), 9);
+        assert_eq!(count(PlayableTetrisPieceType::I), 17);
+        assert_eq!(count(PlayableTetrisPieceType::S), 17);
+        assert_eq!(count(PlayableTetrisPieceType::L), 34);
+    }
+
+    #[test]
+    fn test_finds_tucked_t_spin_double() {
+        let board = board(&["**        ", "*   ******", "** *******"]);
+        let piece =
+            TetrisPieceWithPosition::new(0, 4, TetrisPiece::new(PlayableTetrisPieceType::T));
+
+        let moves = generate_moves(&board, &piece, &PlannerOptions::default());
+
+        let is_tsd = |m: &&GeneratedMove| {
+            let p = m.placement();
+            p.rotation == TetrisPieceRotation::TWO && p.row == board.rows - 3 && p.col == 1
+        };
+        let tsd = moves
+            .iter()
+            .find(is_tsd)
+            .expect("the T-spin slot is reachable");
+        assert!(tsd.rotated_last);
+        assert_eq!(tsd.spin(&board, SpinRule::TSpin), SpinType::Full);
+
+        let hard_drops_only = PlannerOptions {
+            soft_drop: false,
+            ..PlannerOptions::default()
+        };
+        let moves = generate_moves(&board, &piece, &hard_drops_only);
+        assert!(!moves.iter().any(|m| is_tsd(&m)));
+    }
+}
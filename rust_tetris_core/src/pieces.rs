@@ -1,51 +1,75 @@
-use crate::board::{playable_piece_to_cell, TetrisBoard, TetrisCell};
-use crate::constants::{Kick, DEFAULT_KICKS, I_KICKS, NEXT_ROTATIONS, PREV_ROTATIONS};
+use std::sync::Arc;
+
+use crate::board::{playable_piece_to_cell, TetrisBoard};
+use crate::constants::{Kick, RotationKick, NEXT_ROTATIONS, OPPOSITE_ROTATIONS, PREV_ROTATIONS};
 use crate::enums::{PlayableTetrisPieceType, TetrisPieceRotation};
+use crate::rotation::{Kicks180Type, RotationSystem, SRS};
 
+#[derive(Clone)]
 pub struct TetrisPiece {
     pub piece_type: PlayableTetrisPieceType,
-    pub board: TetrisBoard,
     pub rotation: TetrisPieceRotation,
+    pub rotation_system: &'static dyn RotationSystem,
+    pub kicks_180: Kicks180Type,
+    boards: Arc<[TetrisBoard; 4]>,
 }
 
 impl TetrisPiece {
     pub fn new(piece: PlayableTetrisPieceType) -> Self {
-        let mut tetris_piece = TetrisPiece {
+        TetrisPiece::with_rotation_system(piece, &SRS)
+    }
+
+    pub fn with_rotation_system(
+        piece: PlayableTetrisPieceType,
+        rotation_system: &'static dyn RotationSystem,
+    ) -> Self {
+        let board = |rotation| get_piece_matrix(piece, rotation, rotation_system);
+
+        TetrisPiece {
             piece_type: piece,
             rotation: TetrisPieceRotation::ZERO,
-            board: TetrisBoard::new(0, 0),
-        };
-
-        tetris_piece.setup_board();
+            rotation_system,
+            kicks_180: Kicks180Type::default(),
+            boards: Arc::new([
+                board(TetrisPieceRotation::ZERO),
+                board(TetrisPieceRotation::RIGHT),
+                board(TetrisPieceRotation::TWO),
+                board(TetrisPieceRotation::LEFT),
+            ]),
+        }
+    }
 
-        tetris_piece
+    pub fn with_kicks_180(mut self, kicks_180: Kicks180Type) -> Self {
+        self.kicks_180 = kicks_180;
+        self
     }
 
-    fn setup_board(&mut self) {
-        self.board = get_piece_matrix(self.piece_type, self.rotation);
+    pub fn board(&self) -> &TetrisBoard {
+        &self.boards[self.rotation as usize]
     }
 
     pub fn rotate_piece(&mut self) {
         self.rotation = *NEXT_ROTATIONS.get(&self.rotation).unwrap();
-        self.setup_board();
     }
 
     pub fn rotate_piece_prev(&mut self) {
         self.rotation = *PREV_ROTATIONS.get(&self.rotation).unwrap();
-        self.setup_board();
+    }
+
+    pub fn rotate_piece_180(&mut self) {
+        self.rotation = *OPPOSITE_ROTATIONS.get(&self.rotation).unwrap();
     }
 
     pub fn set_rotation(&mut self, rotation: TetrisPieceRotation) {
         self.rotation = rotation;
-        self.setup_board();
     }
 
     pub fn width(&self) -> isize {
-        self.board.cols
+        self.board().cols
     }
 
     pub fn height(&self) -> isize {
-        self.board.rows
+        self.board().rows
     }
 
     pub fn all_cells(&self) -> impl Iterator<Item = (isize, isize)> {
@@ -57,120 +81,90 @@ impl TetrisPiece {
 
     pub fn set_cells(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
         self.all_cells()
-            .filter(move |&(i, j)| self.board.is_set(i, j))
+            .filter(move |&(i, j)| self.board().is_set(i, j))
     }
 
-    pub fn collides_left(&self, row: isize, col: isize, matrix: &TetrisBoard) -> bool {
-        for (i, j) in self.all_cells() {
-            if j + col == 0 {
-                return false;
-            }
-
-            if self.board.is_set(i, j) && matrix.is_set(row + i, j + col - 1) {
-                return true;
-            }
-        }
+    pub fn collides_at(&self, row: isize, col: isize, matrix: &TetrisBoard) -> bool {
+        matrix.collides(self.board(), row, col)
+    }
 
-        false
+    pub fn collides_left(&self, row: isize, col: isize, matrix: &TetrisBoard) -> bool {
+        self.collides_at(row, col - 1, matrix)
     }
 
     pub fn collides_right(&self, row: isize, col: isize, matrix: &TetrisBoard) -> bool {
-        for (i, j) in self.all_cells() {
-            if j + col == self.board.cols - 1 {
-                return false;
-            }
-
-            if self.board.is_set(i, j) && matrix.is_set(row + i, j + col + 1) {
-                return true;
-            }
-        }
-
-        false
+        self.collides_at(row, col + 1, matrix)
     }
 
     pub fn collides_kick(&self, row: isize, col: isize, matrix: &TetrisBoard, kick: &Kick) -> bool {
-        for (i, j) in self.set_cells() {
-            let ei = row + i - kick.1;
-            let ej = j + col + kick.0;
-
-            if ei < 0 || ei >= matrix.rows || ej < 0 || ej >= matrix.cols {
-                return true;
-            }
-
-            if matrix.is_set(ei, ej) {
-                return true;
-            }
-        }
-
-        false
+        self.collides_at(row - kick.1, col + kick.0, matrix)
     }
 
     pub fn collides_on_next(&self, row: isize, col: isize, matrix: &TetrisBoard) -> bool {
-        for (i, j) in self.set_cells() {
-            if row + i == matrix.rows as isize - 1 {
-                return true;
-            }
-
-            if matrix.is_set(row + i + 1, j + col) {
-                return true;
-            }
-        }
-
-        false
+        self.collides_at(row + 1, col, matrix)
     }
 
     pub fn get_kicks(&self, from_rot: TetrisPieceRotation) -> &'static [Kick] {
-        let kick_index = match (from_rot, self.rotation) {
-            (TetrisPieceRotation::ZERO, TetrisPieceRotation::RIGHT) => 0,
-            (TetrisPieceRotation::RIGHT, TetrisPieceRotation::ZERO) => 1,
-            (TetrisPieceRotation::RIGHT, TetrisPieceRotation::TWO) => 2,
-            (TetrisPieceRotation::TWO, TetrisPieceRotation::RIGHT) => 3,
-            (TetrisPieceRotation::TWO, TetrisPieceRotation::LEFT) => 4,
-            (TetrisPieceRotation::LEFT, TetrisPieceRotation::TWO) => 5,
-            (TetrisPieceRotation::LEFT, TetrisPieceRotation::ZERO) => 6,
-            (TetrisPieceRotation::ZERO, TetrisPieceRotation::LEFT) => 7,
-            _ => unreachable!(),
-        };
-
-        match self.piece_type {
-            PlayableTetrisPieceType::I => &I_KICKS[kick_index],
-            PlayableTetrisPieceType::O => &[(0, 0)],
-            _ => &DEFAULT_KICKS[kick_index],
+        if *OPPOSITE_ROTATIONS.get(&from_rot).unwrap() == self.rotation {
+            self.kicks_180.kicks(self.piece_type, from_rot)
+        } else {
+            self.rotation_system
+                .kicks(self.piece_type, from_rot, self.rotation)
         }
     }
+
+    /// First kick from `from_rot` that fits at `row`, `col`, the piece being
+    /// already in its new rotation.
+    pub fn find_kick(
+        &self,
+        from_rot: TetrisPieceRotation,
+        row: isize,
+        col: isize,
+        matrix: &TetrisBoard,
+    ) -> Option<RotationKick> {
+        let half_turn = *OPPOSITE_ROTATIONS.get(&from_rot).unwrap() == self.rotation;
+        let kicks = self.get_kicks(from_rot);
+        let can_kick = self.rotation_system.can_kick(self, row, col, matrix);
+        let tries = if can_kick { kicks.len() } else { 1 };
+
+        kicks[..tries]
+            .iter()
+            .position(|kick| !self.collides_kick(row, col, matrix, kick))
+            .map(|i| RotationKick {
+                offset: kicks[i],
+                fifth_test: i == 4 && !half_turn,
+            })
+    }
 }
 
 fn fill_piece_matrix(
     piece: PlayableTetrisPieceType,
     matrix: &mut TetrisBoard,
     rotation: TetrisPieceRotation,
+    rotation_system: &dyn RotationSystem,
 ) {
-    let matrix_bytes = get_rotations(piece, rotation);
+    let matrix_bytes = rotation_system.shape(piece, rotation);
     let cols = matrix.cols;
 
-    for (row, row_vec) in matrix_bytes.into_iter().zip(matrix.rows_mut()) {
-        let mut acc = 1u8 << (cols - 1);
-        for col in row_vec {
-            let ch = match row & acc {
-                0 => None,
-                _ => Some(piece),
-            };
-
-            *col = ch
-                .map(playable_piece_to_cell)
-                .unwrap_or(TetrisCell::EmptyCell);
-
-            acc >>= 1;
+    for (i, row) in matrix_bytes.into_iter().enumerate() {
+        for j in 0..cols {
+            if row & (1u8 << (cols - 1 - j)) != 0 {
+                matrix.set_val(i as isize, j, playable_piece_to_cell(piece));
+            }
         }
     }
 }
 
-fn get_piece_matrix(piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> TetrisBoard {
+fn get_piece_matrix(
+    piece: PlayableTetrisPieceType,
+    rotation: TetrisPieceRotation,
+    rotation_system: &dyn RotationSystem,
+) -> TetrisBoard {
     let (r, c) = get_piece_size(piece);
 
     let mut matrix = TetrisBoard::new(r, c);
 
-    fill_piece_matrix(piece, &mut matrix, rotation);
+    fill_piece_matrix(piece, &mut matrix, rotation, rotation_system);
 
     matrix
 }
@@ -183,89 +177,12 @@ fn get_piece_size(piece: PlayableTetrisPieceType) -> (isize, isize) {
     }
 }
 
-fn get_rotations(piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8> {
-    piece!(
-        piece,
-        O => get_rotations_o(rotation),
-        I => get_rotations_i(rotation),
-        Z => get_rotations_z(rotation),
-        S => get_rotations_s(rotation),
-        J => get_rotations_j(rotation),
-        L => get_rotations_l(rotation),
-        T => get_rotations_t(rotation),
-    )
-}
-
-fn get_rotations_o(rotation: TetrisPieceRotation) -> Vec<u8> {
-    same_rotation!(rotation, [6, 6, 0])
-}
-
-fn get_rotations_i(rotation: TetrisPieceRotation) -> Vec<u8> {
-    rotations!(
-        rotation,
-        Z => [0, 15, 0, 0],
-        R => [2, 2, 2, 2],
-        T => [0, 0, 15, 0],
-        L => [4, 4, 4, 4]
-    )
-}
-
-fn get_rotations_z(rotation: TetrisPieceRotation) -> Vec<u8> {
-    rotations!(
-        rotation,
-        Z => [6, 3, 0],
-        R => [1, 3, 2],
-        T => [0, 6, 3],
-        L => [2, 6, 4]
-    )
-}
-
-fn get_rotations_s(rotation: TetrisPieceRotation) -> Vec<u8> {
-    rotations!(
-        rotation,
-        Z => [3, 6, 0],
-        R => [2, 3, 1],
-        T => [0, 3, 6],
-        L => [4, 6, 2]
-    )
-}
-
-fn get_rotations_j(rotation: TetrisPieceRotation) -> Vec<u8> {
-    rotations!(
-        rotation,
-        Z => [4, 7, 0],
-        R => [3, 2, 2],
-        T => [0, 7, 1],
-        L => [2, 2, 6]
-    )
-}
-
-fn get_rotations_l(rotation: TetrisPieceRotation) -> Vec<u8> {
-    rotations!(
-        rotation,
-        Z => [1, 7, 0],
-        R => [2, 2, 3],
-        T => [0, 7, 4],
-        L => [6, 2, 2]
-    )
-}
-
-fn get_rotations_t(rotation: TetrisPieceRotation) -> Vec<u8> {
-    rotations!(
-        rotation,
-        Z => [2, 7, 0],
-        R => [2, 3, 2],
-        T => [0, 7, 2],
-        L => [2, 6, 2]
-    )
-}
-
 #[cfg(test)]
 #[allow(non_snake_case)]
 mod tests {
     use super::{
         get_piece_size, PlayableTetrisPieceType, TetrisPiece, TetrisPieceRotation, NEXT_ROTATIONS,
-        PREV_ROTATIONS,
+        OPPOSITE_ROTATIONS, PREV_ROTATIONS,
     };
 
     fn next_rotation(r: TetrisPieceRotation) -> TetrisPieceRotation {
@@ -276,6 +193,10 @@ mod tests {
         *PREV_ROTATIONS.get(&r).unwrap()
     }
 
+    fn opposite_rotation(r: TetrisPieceRotation) -> TetrisPieceRotation {
+        *OPPOSITE_ROTATIONS.get(&r).unwrap()
+    }
+
     #[test]
     fn test_get_piece_size() {
         let (w, h) = get_piece_size(PlayableTetrisPieceType::T);
@@ -338,6 +259,35 @@ mod tests {
         assert_eq!(rotation, TetrisPieceRotation::ZERO);
     }
 
+    #[test]
+    fn test_opposite_rotation() {
+        assert_eq!(
+            opposite_rotation(TetrisPieceRotation::ZERO),
+            TetrisPieceRotation::TWO
+        );
+        assert_eq!(
+            opposite_rotation(TetrisPieceRotation::TWO),
+            TetrisPieceRotation::ZERO
+        );
+        assert_eq!(
+            opposite_rotation(TetrisPieceRotation::RIGHT),
+            TetrisPieceRotation::LEFT
+        );
+        assert_eq!(
+            opposite_rotation(TetrisPieceRotation::LEFT),
+            TetrisPieceRotation::RIGHT
+        );
+    }
+
+    #[test]
+    fn test_half_turn_uses_180_kicks() {
+        let mut t = TetrisPiece::new(PlayableTetrisPieceType::T);
+        t.rotate_piece_180();
+
+        assert_eq!(t.rotation, TetrisPieceRotation::TWO);
+        assert_eq!(t.get_kicks(TetrisPieceRotation::ZERO).len(), 6);
+    }
+
     #[test]
     fn test_set_cells_T() {
         let t = TetrisPiece::new(PlayableTetrisPieceType::T);
@@ -0,0 +1,335 @@
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+use crate::{
+    board::TetrisBoard,
+    engine_structs::TetrisPieceWithPosition,
+    enums::{PlayableTetrisPieceType, TetrisPieceRotation},
+    pieces::TetrisPiece,
+};
+
+/// Where a piece ends up once locked, `row` and `col` being the top left
+/// corner of its rotation box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Placement {
+    pub piece: PlayableTetrisPieceType,
+    pub rotation: TetrisPieceRotation,
+    pub row: isize,
+    pub col: isize,
+}
+
+impl Placement {
+    pub fn of(piece: &TetrisPieceWithPosition) -> Self {
+        Placement {
+            piece: piece.tetris_piece_ref().piece_type,
+            rotation: piece.tetris_piece_ref().rotation,
+            row: piece.row(),
+            col: piece.col(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedInput {
+    Left,
+    Right,
+    // held with a charged DAS until the piece reaches a wall or the stack
+    DasLeft,
+    DasRight,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    // down to the floor without locking
+    SoftDrop,
+    HardDrop,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlannerOptions {
+    pub das: bool,
+    pub soft_drop: bool,
+    pub rotate_180: bool,
+}
+
+impl Default for PlannerOptions {
+    fn default() -> Self {
+        PlannerOptions {
+            das: true,
+            soft_drop: true,
+            rotate_180: true,
+        }
+    }
+}
+
+type State = (isize, isize, TetrisPieceRotation);
+
+fn state_of(piece: &TetrisPieceWithPosition) -> State {
+    (piece.row(), piece.col(), piece.tetris_piece_ref().rotation)
+}
+
+/// Applies `input` to `piece`, returns false if it did not move it. A hard
+/// drop is not a move as it ends the search.
+pub(crate) fn apply_input(
+    piece: &mut TetrisPieceWithPosition,
+    input: PlannedInput,
+    board: &TetrisBoard,
+) -> bool {
+    match input {
+        PlannedInput::Left => piece.try_move_left(board),
+        PlannedInput::Right => piece.try_move_right(board),
+        PlannedInput::DasLeft => {
+            let mut moved = false;
+            while piece.try_move_left(board) {
+                moved = true;
+            }
+            moved
+        }
+        PlannedInput::DasRight => {
+            let mut moved = false;
+            while piece.try_move_right(board) {
+                moved = true;
+            }
+            moved
+        }
+        PlannedInput::RotateCw => rotate(piece, board, TetrisPiece::rotate_piece),
+        PlannedInput::RotateCcw => rotate(piece, board, TetrisPiece::rotate_piece_prev),
+        PlannedInput::Rotate180 => rotate(piece, board, TetrisPiece::rotate_piece_180),
+        PlannedInput::SoftDrop => {
+            let mut moved = false;
+            while !piece.collides_on_next(board) {
+                piece.move_down();
+                moved = true;
+            }
+            moved
+        }
+        PlannedInput::HardDrop => false,
+    }
+}
+
+fn rotate<F: Fn(&mut TetrisPiece)>(
+    piece: &mut TetrisPieceWithPosition,
+    board: &TetrisBoard,
+    rotate_piece: F,
+) -> bool {
+    let prev_rot = piece.tetris_piece_ref().rotation;
+    rotate_piece(piece.tetris_piece_mut());
+
+    match piece.can_rotate(prev_rot, board) {
+        Some(kick) => {
+            piece.kick_by(kick.offset);
+            true
+        }
+        None => {
+            piece.tetris_piece_mut().set_rotation(prev_rot);
+            false
+        }
+    }
+}
+
+pub(crate) fn hard_dropped(
+    piece: &TetrisPieceWithPosition,
+    board: &TetrisBoard,
+) -> TetrisPieceWithPosition {
+    let mut dropped = piece.clone();
+    while !dropped.collides_on_next(board) {
+        dropped.move_down();
+    }
+    dropped
+}
+
+impl PlannerOptions {
+    fn inputs(&self) -> Vec<PlannedInput> {
+        let mut inputs = vec![
+            PlannedInput::Left,
+            PlannedInput::Right,
+            PlannedInput::RotateCw,
+            PlannedInput::RotateCcw,
+        ];
+        if self.rotate_180 {
+            inputs.push(PlannedInput::Rotate180);
+        }
+        if self.das {
+            inputs.extend([PlannedInput::DasLeft, PlannedInput::DasRight]);
+        }
+        if self.soft_drop {
+            inputs.push(PlannedInput::SoftDrop);
+        }
+        inputs
+    }
+}
+
+/// Shortest sequence of inputs, ending with a hard drop, that brings `piece`
+/// to `target`, or None if the placement cannot be reached. Gravity is
+/// ignored, the piece only goes down with soft drops.
+pub fn plan(
+    board: &TetrisBoard,
+    piece: &TetrisPieceWithPosition,
+    target: &Placement,
+    options: &PlannerOptions,
+) -> Option<Vec<PlannedInput>> {
+    if piece.tetris_piece_ref().piece_type != target.piece || piece.collides(board) {
+        return None;
+    }
+
+    let inputs = options.inputs();
+    // every state reached, with the state and input it was reached from
+    let mut parents: HashMap<State, Option<(State, PlannedInput)>> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    parents.insert(state_of(piece), None);
+    queue.push_back(piece.clone());
+
+    while let Some(current) = queue.pop_front() {
+        let state = state_of(&current);
+        if Placement::of(&hard_dropped(&current, board)) == *target {
+            let mut path = vec![PlannedInput::HardDrop];
+            let mut cursor = state;
+            while let Some(Some((parent, input))) = parents.get(&cursor) {
+                path.push(*input);
+                cursor = *parent;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for &input in &inputs {
+            let mut next = current.clone();
+            if !apply_input(&mut next, input, board) {
+                continue;
+            }
+            if let Entry::Vacant(entry) = parents.entry(state_of(&next)) {
+                entry.insert(Some((state, input)));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::TetrisPieceType;
+
+    fn board(rows: &[&str]) -> TetrisBoard {
+        let mut board = TetrisBoard::new(22, 10);
+        let offset = board.rows - rows.len() as isize;
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                if c == '*' {
+                    board.set(
+                        offset + i as isize,
+                        j as isize,
+                        TetrisPieceType::NotPlayable,
+                    );
+                }
+            }
+        }
+        board
+    }
+
+    fn spawned(piece: PlayableTetrisPieceType) -> TetrisPieceWithPosition {
+        TetrisPieceWithPosition::new(0, 4, TetrisPiece::new(piece))
+    }
+
+    fn target(
+        piece: PlayableTetrisPieceType,
+        rotation: TetrisPieceRotation,
+        col: isize,
+    ) -> Placement {
+        let board = board(&[]);
+        let mut tetris_piece = TetrisPiece::new(piece);
+        tetris_piece.set_rotation(rotation);
+        let dropped = hard_dropped(&TetrisPieceWithPosition::new(0, col, tetris_piece), &board);
+        Placement::of(&dropped)
+    }
+
+    #[test]
+    fn test_plans_straight_drop() {
+        let piece = PlayableTetrisPieceType::O;
+        let target = target(piece, TetrisPieceRotation::ZERO, 4);
+
+        let path = plan(
+            &board(&[]),
+            &spawned(piece),
+            &target,
+            &PlannerOptions::default(),
+        );
+
+        assert_eq!(path, Some(vec![PlannedInput::HardDrop]));
+    }
+
+    #[test]
+    fn test_uses_das_and_taps() {
+        let piece = spawned(PlayableTetrisPieceType::O);
+        let options = PlannerOptions::default();
+
+        let mut at_wall = piece.clone();
+        apply_input(&mut at_wall, PlannedInput::DasLeft, &board(&[]));
+        let wall = Placement::of(&hard_dropped(&at_wall, &board(&[])));
+        assert_eq!(
+            plan(&board(&[]), &piece, &wall, &options),
+            Some(vec![PlannedInput::DasLeft, PlannedInput::HardDrop])
+        );
+
+        let no_das = PlannerOptions {
+            das: false,
+            ..options
+        };
+        let taps = (piece.col() - wall.col) as usize;
+        assert_eq!(
+            plan(&board(&[]), &piece, &wall, &no_das),
+            Some(
+                std::iter::repeat_n(PlannedInput::Left, taps)
+                    .chain([PlannedInput::HardDrop])
+                    .collect()
+            )
+        );
+    }
+
+    #[test]
+    fn test_rotates_then_moves() {
+        let piece = PlayableTetrisPieceType::T;
+        let target = target(piece, TetrisPieceRotation::RIGHT, 5);
+
+        let path = plan(
+            &board(&[]),
+            &spawned(piece),
+            &target,
+            &PlannerOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(path.len(), 3);
+        assert!(path.contains(&PlannedInput::RotateCw));
+        assert!(path.contains(&PlannedInput::Right));
+    }
+
+    #[test]
+    fn test_tuck_under_overhang() {
+        let board = board(&["****      ", "          "]);
+        let piece = PlayableTetrisPieceType::I;
+        let target = Placement {
+            piece,
+            rotation: TetrisPieceRotation::ZERO,
+            row: board.rows - 2,
+            col: 0,
+        };
+
+        let path = plan(&board, &spawned(piece), &target, &PlannerOptions::default());
+        assert_eq!(
+            path,
+            Some(vec![
+                PlannedInput::SoftDrop,
+                PlannedInput::DasLeft,
+                PlannedInput::HardDrop
+            ])
+        );
+
+        let no_soft_drop = PlannerOptions {
+            soft_drop: false,
+            ..PlannerOptions::default()
+        };
+        assert_eq!(plan(&board, &spawned(piece), &target, &no_soft_drop), None);
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::enums::PlayableTetrisPieceType;
+
+pub(crate) const PIECES: [PlayableTetrisPieceType; 7] = [
+    PlayableTetrisPieceType::I,
+    PlayableTetrisPieceType::S,
+    PlayableTetrisPieceType::Z,
+    PlayableTetrisPieceType::O,
+    PlayableTetrisPieceType::T,
+    PlayableTetrisPieceType::L,
+    PlayableTetrisPieceType::J,
+];
+
+pub trait Randomizer {
+    fn next_piece(&mut self) -> PlayableTetrisPieceType;
+
+    fn box_clone(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RandomizerType {
+    #[default]
+    SevenBag,
+    FourteenBag,
+    Random,
+    Classic,
+    History4,
+}
+
+impl RandomizerType {
+    pub fn build(self, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerType::SevenBag => Box::new(BagRandomizer::new(seed, 1)),
+            RandomizerType::FourteenBag => Box::new(BagRandomizer::new(seed, 2)),
+            RandomizerType::Random => Box::new(PureRandomizer::new(seed)),
+            RandomizerType::Classic => Box::new(ClassicRandomizer::new(seed)),
+            RandomizerType::History4 => Box::new(HistoryRandomizer::new(seed, 4)),
+        }
+    }
+}
+
+/// Deals shuffled bags containing `copies` of each piece.
+#[derive(Clone)]
+pub struct BagRandomizer {
+    rng: Pcg32,
+    copies: usize,
+    bag: VecDeque<PlayableTetrisPieceType>,
+}
+
+impl BagRandomizer {
+    pub fn new(seed: u64, copies: usize) -> Self {
+        BagRandomizer {
+            rng: Pcg32::seed_from_u64(seed),
+            copies,
+            bag: VecDeque::with_capacity(PIECES.len() * copies),
+        }
+    }
+
+    fn fill_bag(&mut self) {
+        let mut pieces: Vec<_> = (0..self.copies)
+            .flat_map(|_| PIECES.iter().copied())
+            .collect();
+        pieces.as_mut_slice().shuffle(&mut self.rng);
+        self.bag.extend(pieces);
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next_piece(&mut self) -> PlayableTetrisPieceType {
+        if self.bag.is_empty() {
+            self.fill_bag();
+        }
+
+        self.bag.pop_front().unwrap()
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone)]
+pub struct PureRandomizer {
+    rng: Pcg32,
+}
+
+impl PureRandomizer {
+    pub fn new(seed: u64) -> Self {
+        PureRandomizer {
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Randomizer for PureRandomizer {
+    fn next_piece(&mut self) -> PlayableTetrisPieceType {
+        *PIECES.choose(&mut self.rng).unwrap()
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// NES style: rolls eight outcomes, rerolling once on the dummy outcome or on a repeat.
+#[derive(Clone)]
+pub struct ClassicRandomizer {
+    rng: Pcg32,
+    last: Option<PlayableTetrisPieceType>,
+}
+
+impl ClassicRandomizer {
+    pub fn new(seed: u64) -> Self {
+        ClassicRandomizer {
+            rng: Pcg32::seed_from_u64(seed),
+            last: None,
+        }
+    }
+}
+
+impl Randomizer for ClassicRandomizer {
+    fn next_piece(&mut self) -> PlayableTetrisPieceType {
+        let roll = self.rng.gen_range(0, PIECES.len() + 1);
+        let piece = match PIECES.get(roll) {
+            Some(&p) if Some(p) != self.last => p,
+            _ => *PIECES.choose(&mut self.rng).unwrap(),
+        };
+
+        self.last = Some(piece);
+        piece
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// TGM style: retries up to `rolls` times to avoid the last four dealt pieces.
+#[derive(Clone)]
+pub struct HistoryRandomizer {
+    rng: Pcg32,
+    rolls: usize,
+    history: VecDeque<PlayableTetrisPieceType>,
+    first: bool,
+}
+
+impl HistoryRandomizer {
+    pub fn new(seed: u64, rolls: usize) -> Self {
+        HistoryRandomizer {
+            rng: Pcg32::seed_from_u64(seed),
+            rolls,
+            history: VecDeque::from(vec![PlayableTetrisPieceType::Z; 4]),
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn next_piece(&mut self) -> PlayableTetrisPieceType {
+        let piece = if self.first {
+            // the first piece is never an S, Z or O
+            self.first = false;
+            *[
+                PlayableTetrisPieceType::I,
+                PlayableTetrisPieceType::J,
+                PlayableTetrisPieceType::L,
+                PlayableTetrisPieceType::T,
+            ]
+            .choose(&mut self.rng)
+            .unwrap()
+        } else {
+            let mut piece = *PIECES.choose(&mut self.rng).unwrap();
+            for _ in 1..self.rolls {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = *PIECES.choose(&mut self.rng).unwrap();
+            }
+            piece
+        };
+
+        self.history.pop_front();
+        self.history.push_back(piece);
+        piece
+    }
+
+    fn box_clone(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(randomizer: &mut dyn Randomizer, n: usize) -> Vec<PlayableTetrisPieceType> {
+        (0..n).map(|_| randomizer.next_piece()).collect()
+    }
+
+    fn count(pieces: &[PlayableTetrisPieceType], piece: PlayableTetrisPieceType) -> usize {
+        pieces.iter().filter(|&&p| p == piece).count()
+    }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        for &t in &[
+            RandomizerType::SevenBag,
+            RandomizerType::FourteenBag,
+            RandomizerType::Random,
+            RandomizerType::Classic,
+            RandomizerType::History4,
+        ] {
+            let first = take(t.build(42).as_mut(), 100);
+            let second = take(t.build(42).as_mut(), 100);
+            assert_eq!(first, second, "Sequence for {:?} is not reproducible", t);
+        }
+    }
+
+    #[test]
+    fn test_seven_bag_deals_each_piece_once_per_bag() {
+        let mut randomizer = BagRandomizer::new(7, 1);
+
+        for _ in 0..10 {
+            let bag = take(&mut randomizer, 7);
+            for &p in &PIECES {
+                assert_eq!(count(&bag, p), 1, "Bag {:?} is not a permutation", bag);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fourteen_bag_deals_each_piece_twice_per_bag() {
+        let mut randomizer = BagRandomizer::new(14, 2);
+
+        for _ in 0..10 {
+            let bag = take(&mut randomizer, 14);
+            for &p in &PIECES {
+                assert_eq!(
+                    count(&bag, p),
+                    2,
+                    "Bag {:?} is not a double permutation",
+                    bag
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_history_first_piece_is_not_s_z_o() {
+        for seed in 0..50 {
+            let piece = HistoryRandomizer::new(seed, 4).next_piece();
+            assert!(!matches!(
+                piece,
+                PlayableTetrisPieceType::S
+                    | PlayableTetrisPieceType::Z
+                    | PlayableTetrisPieceType::O
+            ));
+        }
+    }
+}
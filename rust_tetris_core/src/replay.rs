@@ -0,0 +1,373 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde::{de, Deserializer, Serializer};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    engine::Game,
+    engine_structs::{GameInput, GameKey, GameParams, TetrisUpdateResult},
+    enums::PlayableTetrisPieceType,
+    randomizer::PIECES,
+};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+const GAME_KEYS: [GameKey; 9] = [
+    GameKey::Left,
+    GameKey::Right,
+    GameKey::NextRotation,
+    GameKey::PrevRotation,
+    GameKey::Rotate180,
+    GameKey::SoftDrop,
+    GameKey::HardDrop,
+    GameKey::SonicDrop,
+    GameKey::Hold,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayAction {
+    Input(GameInput),
+    Undo,
+    Redo,
+    ReceiveGarbage(u32),
+    RemoveLine(usize),
+    SetCurrent(PlayableTetrisPieceType),
+}
+
+/// An action applied before the tick with index `tick` was run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayEvent {
+    pub tick: u64,
+    pub action: ReplayAction,
+}
+
+impl Display for ReplayEvent {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} ", self.tick)?;
+        match self.action {
+            ReplayAction::Input(GameInput::Pressed(key)) => write!(f, "+{:?}", key),
+            ReplayAction::Input(GameInput::Released(key)) => write!(f, "-{:?}", key),
+            ReplayAction::Undo => write!(f, "Undo"),
+            ReplayAction::Redo => write!(f, "Redo"),
+            ReplayAction::ReceiveGarbage(lines) => write!(f, "Garbage {}", lines),
+            ReplayAction::RemoveLine(line) => write!(f, "RemoveLine {}", line),
+            ReplayAction::SetCurrent(piece) => write!(f, "Set {:?}", piece),
+        }
+    }
+}
+
+fn parse_named<T: Copy + fmt::Debug>(values: &[T], s: &str) -> Result<T, String> {
+    values
+        .iter()
+        .find(|v| format!("{:?}", v) == s)
+        .copied()
+        .ok_or_else(|| format!("unknown name {}", s))
+}
+
+impl FromStr for ReplayEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let tick = parts
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| format!("invalid tick in event {:?}", s))?;
+        let name = parts
+            .next()
+            .ok_or_else(|| format!("missing action in event {:?}", s))?;
+        let arg = parts.next();
+
+        let number = |arg: Option<&str>| {
+            arg.and_then(|a| a.parse::<usize>().ok())
+                .ok_or_else(|| format!("invalid argument in event {:?}", s))
+        };
+
+        let action = match name {
+            "Undo" => ReplayAction::Undo,
+            "Redo" => ReplayAction::Redo,
+            "Garbage" => ReplayAction::ReceiveGarbage(number(arg)? as u32),
+            "RemoveLine" => ReplayAction::RemoveLine(number(arg)?),
+            "Set" => ReplayAction::SetCurrent(parse_named(&PIECES, arg.unwrap_or_default())?),
+            _ if name.starts_with('+') => {
+                ReplayAction::Input(GameInput::Pressed(parse_named(&GAME_KEYS, &name[1..])?))
+            }
+            _ if name.starts_with('-') => {
+                ReplayAction::Input(GameInput::Released(parse_named(&GAME_KEYS, &name[1..])?))
+            }
+            _ => return Err(format!("unknown action in event {:?}", s)),
+        };
+
+        Ok(ReplayEvent { tick, action })
+    }
+}
+
+impl serde::Serialize for ReplayEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ReplayEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Everything needed to play a game again: the rules with the seed that was
+/// used and every action with the tick it happened on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub version: u32,
+    pub frames: u64,
+    pub params: GameParams,
+    pub events: Vec<ReplayEvent>,
+}
+
+pub struct ReplayPlayer {
+    replay: Replay,
+    game: Game,
+    next_event: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Result<Self, String> {
+        if replay.version != REPLAY_VERSION {
+            return Err(format!(
+                "unsupported replay version {}, expected {}",
+                replay.version, REPLAY_VERSION
+            ));
+        }
+        if replay.params.seed.is_none() {
+            return Err(String::from("replay without a seed"));
+        }
+
+        let game = Self::new_game(&replay);
+        Ok(ReplayPlayer {
+            replay,
+            game,
+            next_event: 0,
+        })
+    }
+
+    fn new_game(replay: &Replay) -> Game {
+        let mut game = Game::new(replay.params.clone());
+        game.start();
+        game
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.game.frames()
+    }
+
+    pub fn length(&self) -> u64 {
+        self.replay.frames
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let over = self.game.game_over().is_some();
+        match self.replay.events.get(self.next_event) {
+            Some(event) => over && event.tick > self.game.frames(),
+            None => over || self.game.frames() >= self.replay.frames,
+        }
+    }
+
+    /// Applies the actions recorded for the current tick, then runs it.
+    pub fn step(&mut self) -> TetrisUpdateResult {
+        let tick = self.game.frames();
+        let mut inputs = vec![];
+
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.tick > tick {
+                break;
+            }
+            self.next_event += 1;
+
+            let action = event.action;
+            if let ReplayAction::Input(input) = action {
+                inputs.push(input);
+                continue;
+            }
+            // the tick ending the game does not advance the frames, so the
+            // actions that followed it share its index
+            if !inputs.is_empty() {
+                self.game.tick(&inputs);
+                inputs.clear();
+            }
+
+            match action {
+                ReplayAction::Input(_) => {}
+                ReplayAction::Undo => {
+                    self.game.undo();
+                }
+                ReplayAction::Redo => {
+                    self.game.redo();
+                }
+                ReplayAction::ReceiveGarbage(lines) => self.game.receive_garbage(lines),
+                ReplayAction::RemoveLine(line) => self.game.remove_line(line),
+                ReplayAction::SetCurrent(piece) => self.game.set_current(piece),
+            }
+        }
+
+        self.game.tick(&inputs)
+    }
+
+    /// Moves to `tick`, playing again from the start when going backwards.
+    pub fn seek(&mut self, tick: u64) {
+        if tick < self.game.frames() {
+            self.game = Self::new_game(&self.replay);
+            self.next_event = 0;
+        }
+
+        while self.game.frames() < tick && !self.is_finished() {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded_game() -> Game {
+        let params = GameParams {
+            seed: Some(7),
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start();
+
+        let inputs = [
+            GameInput::Pressed(GameKey::Left),
+            GameInput::Released(GameKey::Left),
+            GameInput::Pressed(GameKey::NextRotation),
+            GameInput::Pressed(GameKey::HardDrop),
+            GameInput::Pressed(GameKey::Hold),
+            GameInput::Pressed(GameKey::Right),
+        ];
+        for (i, &input) in inputs.iter().cycle().take(60).enumerate() {
+            game.tick(&[input]);
+            if i == 20 {
+                game.receive_garbage(2);
+            }
+            if i == 40 {
+                game.undo();
+            }
+        }
+        for _ in 0..30 {
+            game.tick(&[]);
+        }
+        game
+    }
+
+    #[test]
+    fn test_event_text_round_trip() {
+        let events = [
+            ReplayEvent {
+                tick: 3,
+                action: ReplayAction::Input(GameInput::Pressed(GameKey::Rotate180)),
+            },
+            ReplayEvent {
+                tick: 4,
+                action: ReplayAction::Input(GameInput::Released(GameKey::SoftDrop)),
+            },
+            ReplayEvent {
+                tick: 10,
+                action: ReplayAction::ReceiveGarbage(4),
+            },
+            ReplayEvent {
+                tick: 11,
+                action: ReplayAction::SetCurrent(PlayableTetrisPieceType::T),
+            },
+            ReplayEvent {
+                tick: 12,
+                action: ReplayAction::Undo,
+            },
+        ];
+
+        for event in &events {
+            assert_eq!(event.to_string().parse::<ReplayEvent>(), Ok(*event));
+        }
+        assert_eq!(events[0].to_string(), "3 +Rotate180");
+        assert!("5 +Jump".parse::<ReplayEvent>().is_err());
+        assert!("x Undo".parse::<ReplayEvent>().is_err());
+    }
+
+    #[test]
+    fn test_playback_reproduces_game() {
+        let game = recorded_game();
+        let mut player = ReplayPlayer::new(game.replay()).unwrap();
+
+        while !player.is_finished() {
+            player.step();
+        }
+
+        assert_eq!(player.tick(), game.frames());
+        assert_eq!(player.game().score(), game.score());
+        assert_eq!(
+            format!("{:?}", player.game().board()),
+            format!("{:?}", game.board())
+        );
+        assert_eq!(player.game().pending_garbage(), game.pending_garbage());
+    }
+
+    #[test]
+    fn test_playback_keeps_undo_after_game_over() {
+        let params = GameParams {
+            seed: Some(7),
+            ..GameParams::default()
+        };
+        let mut game = Game::new(params);
+        game.start();
+        game.receive_garbage(30);
+        for _ in 0..30 {
+            game.tick(&[]);
+        }
+        game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+        assert!(game.game_over().is_some());
+
+        // recorded on the tick that ended the game
+        game.undo();
+        for _ in 0..10 {
+            game.tick(&[]);
+        }
+        assert_eq!(game.game_over(), None);
+
+        let mut player = ReplayPlayer::new(game.replay()).unwrap();
+        while !player.is_finished() {
+            player.step();
+        }
+
+        assert_eq!(player.game().game_over(), None);
+        assert_eq!(player.tick(), game.frames());
+    }
+
+    #[test]
+    fn test_seek_backwards_replays_from_start() {
+        let game = recorded_game();
+        let mut player = ReplayPlayer::new(game.replay()).unwrap();
+
+        player.seek(50);
+        let board = format!("{:?}", player.game().board());
+        player.seek(80);
+        player.seek(50);
+
+        assert_eq!(player.tick(), 50);
+        assert_eq!(format!("{:?}", player.game().board()), board);
+    }
+
+    #[test]
+    fn test_rejects_other_versions() {
+        let mut replay = recorded_game().replay();
+        replay.version = REPLAY_VERSION + 1;
+
+        assert!(ReplayPlayer::new(replay).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    board::TetrisBoard,
+    constants::{
+        Kick, ARS_KICKS, DEFAULT_KICKS, I_KICKS, NO_KICKS, NULLPOMINO_180_KICKS,
+        NULLPOMINO_I_180_KICKS, SRS_PLUS_I_KICKS, TETRIO_180_KICKS,
+    },
+    enums::{PlayableTetrisPieceType, TetrisPieceRotation},
+    pieces::TetrisPiece,
+};
+
+/// Supplies the piece shapes for every rotation and the kicks tried when rotating.
+///
+/// Shapes are given as one bitmask per row of the piece matrix, most significant
+/// bit on the left.
+pub trait RotationSystem: Sync {
+    fn shape(&self, piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8>;
+
+    fn kicks(
+        &self,
+        piece: PlayableTetrisPieceType,
+        from: TetrisPieceRotation,
+        to: TetrisPieceRotation,
+    ) -> &'static [Kick];
+
+    /// Called with the piece already in the target rotation, before any kick is tried.
+    fn can_kick(
+        &self,
+        _piece: &TetrisPiece,
+        _row: isize,
+        _col: isize,
+        _matrix: &TetrisBoard,
+    ) -> bool {
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationSystemType {
+    #[default]
+    Srs,
+    SrsPlus,
+    Ars,
+    Nrs,
+}
+
+impl RotationSystemType {
+    pub fn system(self) -> &'static dyn RotationSystem {
+        match self {
+            RotationSystemType::Srs => &SRS,
+            RotationSystemType::SrsPlus => &SRS_PLUS,
+            RotationSystemType::Ars => &ARS,
+            RotationSystemType::Nrs => &NRS,
+        }
+    }
+}
+
+/// Kick table used for half turns, independent from the rotation system.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Kicks180Type {
+    None,
+    #[default]
+    TetrIo,
+    Nullpomino,
+}
+
+impl Kicks180Type {
+    pub fn kicks(
+        self,
+        piece: PlayableTetrisPieceType,
+        from: TetrisPieceRotation,
+    ) -> &'static [Kick] {
+        let kick_index = match from {
+            TetrisPieceRotation::ZERO => 0,
+            TetrisPieceRotation::RIGHT => 1,
+            TetrisPieceRotation::TWO => 2,
+            TetrisPieceRotation::LEFT => 3,
+        };
+
+        match (self, piece) {
+            (Kicks180Type::None, _) | (_, PlayableTetrisPieceType::O) => &NO_KICKS,
+            (Kicks180Type::TetrIo, _) => &TETRIO_180_KICKS[kick_index],
+            (Kicks180Type::Nullpomino, PlayableTetrisPieceType::I) => {
+                &NULLPOMINO_I_180_KICKS[kick_index]
+            }
+            (Kicks180Type::Nullpomino, _) => &NULLPOMINO_180_KICKS[kick_index],
+        }
+    }
+}
+
+pub static SRS: Srs = Srs;
+pub static SRS_PLUS: SrsPlus = SrsPlus;
+pub static ARS: Ars = Ars;
+pub static NRS: Nrs = Nrs;
+
+pub(crate) fn kick_index(from: TetrisPieceRotation, to: TetrisPieceRotation) -> usize {
+    match (from, to) {
+        (TetrisPieceRotation::ZERO, TetrisPieceRotation::RIGHT) => 0,
+        (TetrisPieceRotation::RIGHT, TetrisPieceRotation::ZERO) => 1,
+        (TetrisPieceRotation::RIGHT, TetrisPieceRotation::TWO) => 2,
+        (TetrisPieceRotation::TWO, TetrisPieceRotation::RIGHT) => 3,
+        (TetrisPieceRotation::TWO, TetrisPieceRotation::LEFT) => 4,
+        (TetrisPieceRotation::LEFT, TetrisPieceRotation::TWO) => 5,
+        (TetrisPieceRotation::LEFT, TetrisPieceRotation::ZERO) => 6,
+        (TetrisPieceRotation::ZERO, TetrisPieceRotation::LEFT) => 7,
+        _ => unreachable!(),
+    }
+}
+
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn shape(&self, piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8> {
+        srs_shape(piece, rotation)
+    }
+
+    fn kicks(
+        &self,
+        piece: PlayableTetrisPieceType,
+        from: TetrisPieceRotation,
+        to: TetrisPieceRotation,
+    ) -> &'static [Kick] {
+        let kick_index = kick_index(from, to);
+
+        match piece {
+            PlayableTetrisPieceType::I => &I_KICKS[kick_index],
+            PlayableTetrisPieceType::O => &NO_KICKS,
+            _ => &DEFAULT_KICKS[kick_index],
+        }
+    }
+}
+
+/// SRS with the symmetric I kicks used by TETR.IO.
+pub struct SrsPlus;
+
+impl RotationSystem for SrsPlus {
+    fn shape(&self, piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8> {
+        srs_shape(piece, rotation)
+    }
+
+    fn kicks(
+        &self,
+        piece: PlayableTetrisPieceType,
+        from: TetrisPieceRotation,
+        to: TetrisPieceRotation,
+    ) -> &'static [Kick] {
+        match piece {
+            PlayableTetrisPieceType::I => &SRS_PLUS_I_KICKS[kick_index(from, to)],
+            _ => SRS.kicks(piece, from, to),
+        }
+    }
+}
+
+/// Arika rotation system (TGM): bottom aligned shapes, one column kicks to the
+/// right and then to the left, never for the I piece.
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn shape(&self, piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8> {
+        piece!(
+            piece,
+            O => same_rotation!(rotation, [6, 6, 0]),
+            I => rotations!(
+                rotation,
+                Z => [0, 15, 0, 0],
+                R => [2, 2, 2, 2],
+                T => [0, 15, 0, 0],
+                L => [2, 2, 2, 2]
+            ),
+            Z => rotations!(
+                rotation,
+                Z => [0, 6, 3],
+                R => [1, 3, 2],
+                T => [0, 6, 3],
+                L => [1, 3, 2]
+            ),
+            S => rotations!(
+                rotation,
+                Z => [0, 3, 6],
+                R => [4, 6, 2],
+                T => [0, 3, 6],
+                L => [4, 6, 2]
+            ),
+            J => rotations!(
+                rotation,
+                Z => [0, 7, 1],
+                R => [2, 2, 6],
+                T => [0, 4, 7],
+                L => [3, 2, 2]
+            ),
+            L => rotations!(
+                rotation,
+                Z => [0, 7, 4],
+                R => [6, 2, 2],
+                T => [0, 1, 7],
+                L => [2, 2, 3]
+            ),
+            T => rotations!(
+                rotation,
+                Z => [0, 7, 2],
+                R => [2, 6, 2],
+                T => [0, 2, 7],
+                L => [2, 3, 2]
+            ),
+        )
+    }
+
+    fn kicks(
+        &self,
+        piece: PlayableTetrisPieceType,
+        _from: TetrisPieceRotation,
+        _to: TetrisPieceRotation,
+    ) -> &'static [Kick] {
+        match piece {
+            PlayableTetrisPieceType::I | PlayableTetrisPieceType::O => &NO_KICKS,
+            _ => &ARS_KICKS,
+        }
+    }
+
+    fn can_kick(&self, piece: &TetrisPiece, row: isize, col: isize, matrix: &TetrisBoard) -> bool {
+        // center column rule: L, J and T do not kick when the first blocked
+        // cell, scanning row by row, lies in the middle column
+        let center_rule = matches!(
+            piece.piece_type,
+            PlayableTetrisPieceType::L | PlayableTetrisPieceType::J | PlayableTetrisPieceType::T
+        );
+
+        if !center_rule {
+            return true;
+        }
+
+        piece
+            .set_cells()
+            .find(|&(i, j)| {
+                let (ei, ej) = (row + i, col + j);
+                !matrix.is_in_bounds(ei, ej) || matrix.is_set(ei, ej)
+            })
+            .map(|(_, j)| j != 1)
+            .unwrap_or(true)
+    }
+}
+
+/// Nintendo rotation system: right handed, no kicks at all.
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn shape(&self, piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8> {
+        piece!(
+            piece,
+            O => same_rotation!(rotation, [6, 6, 0]),
+            I => rotations!(
+                rotation,
+                Z => [0, 0, 15, 0],
+                R => [2, 2, 2, 2],
+                T => [0, 0, 15, 0],
+                L => [2, 2, 2, 2]
+            ),
+            Z => rotations!(
+                rotation,
+                Z => [0, 6, 3],
+                R => [1, 3, 2],
+                T => [0, 6, 3],
+                L => [1, 3, 2]
+            ),
+            S => rotations!(
+                rotation,
+                Z => [0, 3, 6],
+                R => [2, 3, 1],
+                T => [0, 3, 6],
+                L => [2, 3, 1]
+            ),
+            J => rotations!(
+                rotation,
+                Z => [0, 7, 1],
+                R => [2, 2, 6],
+                T => [4, 7, 0],
+                L => [3, 2, 2]
+            ),
+            L => rotations!(
+                rotation,
+                Z => [0, 7, 4],
+                R => [6, 2, 2],
+                T => [1, 7, 0],
+                L => [2, 2, 3]
+            ),
+            T => rotations!(
+                rotation,
+                Z => [0, 7, 2],
+                R => [2, 6, 2],
+                T => [2, 7, 0],
+                L => [2, 3, 2]
+            ),
+        )
+    }
+
+    fn kicks(
+        &self,
+        _piece: PlayableTetrisPieceType,
+        _from: TetrisPieceRotation,
+        _to: TetrisPieceRotation,
+    ) -> &'static [Kick] {
+        &NO_KICKS
+    }
+}
+
+fn srs_shape(piece: PlayableTetrisPieceType, rotation: TetrisPieceRotation) -> Vec<u8> {
+    piece!(
+        piece,
+        O => same_rotation!(rotation, [6, 6, 0]),
+        I => rotations!(
+            rotation,
+            Z => [0, 15, 0, 0],
+            R => [2, 2, 2, 2],
+            T => [0, 0, 15, 0],
+            L => [4, 4, 4, 4]
+        ),
+        Z => rotations!(
+            rotation,
+            Z => [6, 3, 0],
+            R => [1, 3, 2],
+            T => [0, 6, 3],
+            L => [2, 6, 4]
+        ),
+        S => rotations!(
+            rotation,
+            Z => [3, 6, 0],
+            R => [2, 3, 1],
+            T => [0, 3, 6],
+            L => [4, 6, 2]
+        ),
+        J => rotations!(
+            rotation,
+            Z => [4, 7, 0],
+            R => [3, 2, 2],
+            T => [0, 7, 1],
+            L => [2, 2, 6]
+        ),
+        L => rotations!(
+            rotation,
+            Z => [1, 7, 0],
+            R => [2, 2, 3],
+            T => [0, 7, 4],
+            L => [6, 2, 2]
+        ),
+        T => rotations!(
+            rotation,
+            Z => [2, 7, 0],
+            R => [2, 3, 2],
+            T => [0, 7, 2],
+            L => [2, 6, 2]
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::not_playable_piece_to_cell;
+
+    const ALL_PIECES: [PlayableTetrisPieceType; 7] = [
+        PlayableTetrisPieceType::I,
+        PlayableTetrisPieceType::O,
+        PlayableTetrisPieceType::T,
+        PlayableTetrisPieceType::S,
+        PlayableTetrisPieceType::Z,
+        PlayableTetrisPieceType::J,
+        PlayableTetrisPieceType::L,
+    ];
+
+    const ALL_ROTATIONS: [TetrisPieceRotation; 4] = [
+        TetrisPieceRotation::ZERO,
+        TetrisPieceRotation::RIGHT,
+        TetrisPieceRotation::TWO,
+        TetrisPieceRotation::LEFT,
+    ];
+
+    #[test]
+    fn test_every_shape_has_four_cells() {
+        for &t in &[
+            RotationSystemType::Srs,
+            RotationSystemType::SrsPlus,
+            RotationSystemType::Ars,
+            RotationSystemType::Nrs,
+        ] {
+            for &p in &ALL_PIECES {
+                for &r in &ALL_ROTATIONS {
+                    let cells: u32 = t.system().shape(p, r).iter().map(|b| b.count_ones()).sum();
+                    assert_eq!(cells, 4, "{:?} {:?} {:?} has {} cells", t, p, r, cells);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_srs_plus_i_kicks_are_symmetric() {
+        let cw = SRS_PLUS.kicks(
+            PlayableTetrisPieceType::I,
+            TetrisPieceRotation::ZERO,
+            TetrisPieceRotation::RIGHT,
+        );
+        let ccw = SRS_PLUS.kicks(
+            PlayableTetrisPieceType::I,
+            TetrisPieceRotation::ZERO,
+            TetrisPieceRotation::LEFT,
+        );
+
+        for (a, b) in cw.iter().zip(ccw.iter()).take(3) {
+            assert_eq!(a.0, -b.0);
+            assert_eq!(a.1, b.1);
+        }
+    }
+
+    #[test]
+    fn test_180_kicks_start_in_place() {
+        for &t in &[
+            Kicks180Type::None,
+            Kicks180Type::TetrIo,
+            Kicks180Type::Nullpomino,
+        ] {
+            for &p in &ALL_PIECES {
+                for &r in &ALL_ROTATIONS {
+                    assert_eq!(t.kicks(p, r)[0], (0, 0));
+                }
+            }
+        }
+
+        assert_eq!(
+            Kicks180Type::None.kicks(PlayableTetrisPieceType::T, TetrisPieceRotation::ZERO),
+            &[(0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_nrs_never_kicks() {
+        for &p in &ALL_PIECES {
+            let kicks = NRS.kicks(p, TetrisPieceRotation::ZERO, TetrisPieceRotation::RIGHT);
+            assert_eq!(kicks, &[(0, 0)]);
+        }
+    }
+
+    #[test]
+    fn test_ars_center_column_rule() {
+        let mut matrix = TetrisBoard::new(3, 3);
+        matrix.set_val(0, 1, not_playable_piece_to_cell());
+
+        let mut t = TetrisPiece::with_rotation_system(PlayableTetrisPieceType::T, &ARS);
+        t.set_rotation(TetrisPieceRotation::RIGHT);
+        assert!(!ARS.can_kick(&t, 0, 0, &matrix));
+
+        let mut matrix = TetrisBoard::new(3, 3);
+        matrix.set_val(1, 0, not_playable_piece_to_cell());
+        assert!(ARS.can_kick(&t, 0, 0, &matrix));
+    }
+}
@@ -0,0 +1,210 @@
+use serde_derive::{Deserialize, Serialize};
+
+const LINES_PER_LEVEL: u32 = 10;
+const MAX_LEVEL: u32 = 20;
+pub const DEFAULT_TICK_RATE: u32 = 60;
+// 20G, in cells per tick at the default tick rate
+const MAX_GRAVITY: f64 = 20.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GravityCurve {
+    Fixed,
+    #[default]
+    Guideline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpinType {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineClear {
+    pub lines: u32,
+    pub spin: SpinType,
+    pub back_to_back: bool,
+    pub combo: u32,
+    pub perfect_clear: bool,
+}
+
+impl LineClear {
+    // zero-line spins get the bonus too, though only line clears extend or break the chain
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || self.spin != SpinType::None
+    }
+}
+
+pub fn line_clear_points(clear: &LineClear) -> u32 {
+    let base = match (clear.spin, clear.lines) {
+        (SpinType::None, 0) => 0,
+        (SpinType::None, 1) => 100,
+        (SpinType::None, 2) => 300,
+        (SpinType::None, 3) => 500,
+        (SpinType::None, _) => 800,
+        (SpinType::Mini, 0) => 100,
+        (SpinType::Mini, 1) => 200,
+        (SpinType::Mini, _) => 400,
+        (SpinType::Full, 0) => 400,
+        (SpinType::Full, 1) => 800,
+        (SpinType::Full, 2) => 1200,
+        (SpinType::Full, _) => 1600,
+    };
+
+    let base = if clear.back_to_back && clear.is_difficult() {
+        base * 3 / 2
+    } else {
+        base
+    };
+
+    let perfect_clear = if !clear.perfect_clear {
+        0
+    } else if clear.back_to_back && clear.lines == 4 {
+        3200
+    } else {
+        match clear.lines {
+            1 => 800,
+            2 => 1200,
+            3 => 1800,
+            _ => 2000,
+        }
+    };
+
+    base + 50 * clear.combo + perfect_clear
+}
+
+/// Cells per tick for `level`, from the guideline curve `(0.8 - (level - 1) * 0.007)^(level - 1)`
+/// seconds per row.
+pub fn guideline_gravity(level: u32, tick_rate: u32) -> f64 {
+    let level = level.clamp(1, MAX_LEVEL) as f64;
+    let seconds_per_row = (0.8 - (level - 1.0) * 0.007).powf(level - 1.0);
+
+    let max_gravity = MAX_GRAVITY * DEFAULT_TICK_RATE as f64 / tick_rate as f64;
+
+    (1.0 / (seconds_per_row * tick_rate as f64)).min(max_gravity)
+}
+
+#[derive(Clone)]
+pub struct Scoring {
+    score: u64,
+    lines: u32,
+    start_level: u32,
+}
+
+impl Scoring {
+    pub fn new(start_level: u32) -> Self {
+        Scoring {
+            score: 0,
+            lines: 0,
+            start_level: start_level.max(1),
+        }
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn level(&self) -> u32 {
+        self.start_level + self.lines / LINES_PER_LEVEL
+    }
+
+    /// Awards the clear at the level it was made on, returns the points awarded.
+    pub fn line_clear(&mut self, clear: &LineClear) -> u64 {
+        let points = line_clear_points(clear) as u64 * self.level() as u64;
+        self.score += points;
+        self.lines += clear.lines;
+        points
+    }
+
+    pub fn soft_drop(&mut self, cells: u32) {
+        self.score += cells as u64;
+    }
+
+    pub fn hard_drop(&mut self, cells: u32) {
+        self.score += 2 * cells as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: u32, spin: SpinType) -> LineClear {
+        LineClear {
+            lines,
+            spin,
+            back_to_back: false,
+            combo: 0,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn test_line_clear_points() {
+        assert_eq!(line_clear_points(&clear(1, SpinType::None)), 100);
+        assert_eq!(line_clear_points(&clear(4, SpinType::None)), 800);
+        assert_eq!(line_clear_points(&clear(0, SpinType::Mini)), 100);
+        assert_eq!(line_clear_points(&clear(2, SpinType::Full)), 1200);
+    }
+
+    #[test]
+    fn test_back_to_back_only_boosts_difficult_clears() {
+        let tetris = LineClear {
+            back_to_back: true,
+            ..clear(4, SpinType::None)
+        };
+        let double = LineClear {
+            back_to_back: true,
+            ..clear(2, SpinType::None)
+        };
+        let zero_line_spin = LineClear {
+            back_to_back: true,
+            ..clear(0, SpinType::Full)
+        };
+
+        assert_eq!(line_clear_points(&tetris), 1200);
+        assert_eq!(line_clear_points(&double), 300);
+        assert_eq!(line_clear_points(&zero_line_spin), 600);
+    }
+
+    #[test]
+    fn test_combo_and_perfect_clear_bonus() {
+        let pc = LineClear {
+            combo: 2,
+            perfect_clear: true,
+            ..clear(2, SpinType::None)
+        };
+
+        assert_eq!(line_clear_points(&pc), 300 + 100 + 1200);
+    }
+
+    #[test]
+    fn test_level_multiplies_points_and_advances_every_ten_lines() {
+        let mut scoring = Scoring::new(2);
+
+        for _ in 0..3 {
+            scoring.line_clear(&clear(4, SpinType::None));
+        }
+        scoring.hard_drop(10);
+        scoring.soft_drop(3);
+
+        assert_eq!(scoring.lines(), 12);
+        assert_eq!(scoring.level(), 3);
+        assert_eq!(scoring.score(), 800 * 2 * 3 + 20 + 3);
+    }
+
+    #[test]
+    fn test_guideline_gravity_curve() {
+        assert!((guideline_gravity(1, 60) - 1.0 / 60.0).abs() < 1e-9);
+        assert!((guideline_gravity(1, 120) - 1.0 / 120.0).abs() < 1e-9);
+        assert!(guideline_gravity(10, 60) > guideline_gravity(9, 60));
+        assert_eq!(guideline_gravity(MAX_LEVEL, 60), MAX_GRAVITY);
+        assert_eq!(guideline_gravity(100, 60), guideline_gravity(MAX_LEVEL, 60));
+        assert_eq!(guideline_gravity(MAX_LEVEL, 120), MAX_GRAVITY / 2.0);
+    }
+}
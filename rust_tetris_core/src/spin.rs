@@ -0,0 +1,225 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    board::TetrisBoard, constants::RotationKick, engine_structs::TetrisPieceWithPosition,
+    enums::PlayableTetrisPieceType, scoring::SpinType,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpinRule {
+    #[default]
+    TSpin,
+    AllSpin,
+    AllSpinWithO,
+}
+
+fn is_occupied(board: &TetrisBoard, r: isize, c: isize) -> bool {
+    !board.is_in_bounds(r, c) || board.is_set(r, c)
+}
+
+/// Center cell of the T and the direction its nub points to, read from the
+/// piece cells so that every rotation system is handled.
+fn center_and_facing(piece: &TetrisPieceWithPosition) -> Option<((isize, isize), (isize, isize))> {
+    let cells: Vec<_> = piece.tetris_piece_ref().set_cells().collect();
+    let has = |cell: (isize, isize)| cells.contains(&cell);
+    let dirs = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+    let &(r, c) = cells.iter().find(|&&(r, c)| {
+        dirs.iter()
+            .filter(|&&(dr, dc)| has((r + dr, c + dc)))
+            .count()
+            == 3
+    })?;
+    let &facing = dirs
+        .iter()
+        .find(|&&(dr, dc)| has((r + dr, c + dc)) && !has((r - dr, c - dc)))?;
+
+    Some(((r + piece.row(), c + piece.col()), facing))
+}
+
+/// Three-corner T-spin check, `last_kick` being the kick of the rotation that
+/// last moved the piece.
+pub fn t_spin(
+    piece: &TetrisPieceWithPosition,
+    board: &TetrisBoard,
+    last_kick: Option<RotationKick>,
+) -> SpinType {
+    if piece.tetris_piece_ref().piece_type != PlayableTetrisPieceType::T {
+        return SpinType::None;
+    }
+
+    let ((r, c), (dr, dc)) = match center_and_facing(piece) {
+        Some(v) => v,
+        None => return SpinType::None,
+    };
+
+    // corners on the nub side, then on the flat side
+    let (pr, pc) = (dc, dr);
+    let front = [(r + dr + pr, c + dc + pc), (r + dr - pr, c + dc - pc)];
+    let back = [(r - dr + pr, c - dc + pc), (r - dr - pr, c - dc - pc)];
+
+    let count = |corners: &[(isize, isize)]| {
+        corners
+            .iter()
+            .filter(|&&(i, j)| is_occupied(board, i, j))
+            .count()
+    };
+    let front = count(&front);
+    let back = count(&back);
+
+    if front + back < 3 {
+        SpinType::None
+    } else if front == 2 || last_kick.is_some_and(|k| k.fifth_test) {
+        SpinType::Full
+    } else {
+        SpinType::Mini
+    }
+}
+
+fn is_immobile(piece: &TetrisPieceWithPosition, board: &TetrisBoard) -> bool {
+    let (r, c) = (piece.row(), piece.col());
+    let piece = piece.tetris_piece_ref();
+
+    piece.collides_at(r, c - 1, board)
+        && piece.collides_at(r, c + 1, board)
+        && piece.collides_at(r - 1, c, board)
+}
+
+/// Spin for a piece whose last move was a rotation, checked before it is locked
+/// on `board`. Non-T spins are immobile spins and score as Minis.
+pub fn detect_spin(
+    piece: &TetrisPieceWithPosition,
+    board: &TetrisBoard,
+    last_kick: Option<RotationKick>,
+    rule: SpinRule,
+) -> SpinType {
+    match (piece.tetris_piece_ref().piece_type, rule) {
+        (PlayableTetrisPieceType::T, _) => t_spin(piece, board, last_kick),
+        (_, SpinRule::TSpin) => SpinType::None,
+        (PlayableTetrisPieceType::O, SpinRule::AllSpin) => SpinType::None,
+        _ if is_immobile(piece, board) => SpinType::Mini,
+        _ => SpinType::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        enums::{TetrisPieceRotation, TetrisPieceType},
+        pieces::TetrisPiece,
+    };
+
+    fn board(rows: &[&str]) -> TetrisBoard {
+        let mut board = TetrisBoard::new(rows.len() as isize, rows[0].len() as isize);
+        for (i, row) in rows.iter().enumerate() {
+            for (j, ch) in row.chars().enumerate() {
+                if ch == '*' {
+                    board.set(i as isize, j as isize, TetrisPieceType::NotPlayable);
+                }
+            }
+        }
+        board
+    }
+
+    fn t(r: isize, c: isize, rotations: usize) -> TetrisPieceWithPosition {
+        let mut piece = TetrisPiece::new(PlayableTetrisPieceType::T);
+        for _ in 0..rotations {
+            piece.rotate_piece();
+        }
+        TetrisPieceWithPosition::new(r, c, piece)
+    }
+
+    #[test]
+    fn test_tsd_is_full() {
+        // T pointing down into the slot, both front corners filled
+        let board = board(&["     ", "**   ", "*   *", "** **"]);
+
+        assert_eq!(t_spin(&t(1, 1, 2), &board, None), SpinType::Full);
+    }
+
+    #[test]
+    fn test_one_front_corner_is_mini() {
+        // T pointing up on the floor, the floor fills both back corners
+        let open = board(&["    ", "    ", "    "]);
+        let board = board(&["    ", "*   ", "    "]);
+
+        assert_eq!(t_spin(&t(1, 0, 0), &open, None), SpinType::None);
+        assert_eq!(t_spin(&t(1, 0, 0), &board, None), SpinType::Mini);
+    }
+
+    #[test]
+    fn test_tst_kick_upgrades_mini() {
+        let board = board(&["    ", "*   ", "    "]);
+
+        let kick = |offset, fifth_test| Some(RotationKick { offset, fifth_test });
+
+        assert_eq!(
+            t_spin(&t(1, 0, 0), &board, kick((1, -2), true)),
+            SpinType::Full
+        );
+        assert_eq!(
+            t_spin(&t(1, 0, 0), &board, kick((1, 0), false)),
+            SpinType::Mini
+        );
+    }
+
+    #[test]
+    fn test_180_kick_keeps_mini() {
+        // T facing right flipped left with the (1, 2) kick, one front corner filled
+        let board = board(&[" * * ", "     ", "   * ", "*    ", "  *  "]);
+        let mut piece = t(2, 0, 1);
+        piece.tetris_piece_mut().rotate_piece_180();
+
+        let kick = piece
+            .can_rotate(TetrisPieceRotation::RIGHT, &board)
+            .unwrap();
+        piece.kick_by(kick.offset);
+
+        assert_eq!(kick.offset, (1, 2));
+        assert_eq!(t_spin(&piece, &board, Some(kick)), SpinType::Mini);
+    }
+
+    #[test]
+    fn test_immobile_s_spin() {
+        // S twisted into its slot, blocked on every side
+        let board = board(&["*  *", "   *", "*  *", "** *"]);
+        let mut s = TetrisPiece::new(PlayableTetrisPieceType::S);
+        s.rotate_piece();
+        let piece = TetrisPieceWithPosition::new(0, 0, s);
+
+        assert_eq!(
+            detect_spin(&piece, &board, None, SpinRule::TSpin),
+            SpinType::None
+        );
+        assert_eq!(
+            detect_spin(&piece, &board, None, SpinRule::AllSpin),
+            SpinType::Mini
+        );
+    }
+
+    #[test]
+    fn test_o_spins_only_when_enabled() {
+        let board = board(&["*  *", "*  *", "****"]);
+        let piece =
+            TetrisPieceWithPosition::new(0, 0, TetrisPiece::new(PlayableTetrisPieceType::O));
+
+        assert_eq!(
+            detect_spin(&piece, &board, None, SpinRule::AllSpin),
+            SpinType::None
+        );
+        assert_eq!(
+            detect_spin(&piece, &board, None, SpinRule::AllSpinWithO),
+            SpinType::Mini
+        );
+    }
+
+    #[test]
+    fn test_not_a_t() {
+        let board = board(&["***", "* *", "***"]);
+        let piece =
+            TetrisPieceWithPosition::new(0, 0, TetrisPiece::new(PlayableTetrisPieceType::S));
+
+        assert_eq!(t_spin(&piece, &board, None), SpinType::None);
+    }
+}
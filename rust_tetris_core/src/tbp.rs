@@ -0,0 +1,639 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    board::{TetrisBoard, TetrisCell},
+    bot::{Bot, BotParams, BotState},
+    engine::Game,
+    engine_structs::{GameParams, TetrisPieceWithPosition},
+    enums::{PlayableTetrisPieceType, TetrisPieceRotation, TetrisPieceType},
+    movegen::generate_moves,
+    pieces::TetrisPiece,
+    planner::{Placement, PlannerOptions},
+    rotation::{Kicks180Type, RotationSystem},
+    scoring::SpinType,
+    spin::SpinRule,
+};
+
+// the protocol always sends 40 rows, the bottom one first
+pub const BOARD_ROWS: usize = 40;
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+const ROTATIONS: [TetrisPieceRotation; 4] = [
+    TetrisPieceRotation::ZERO,
+    TetrisPieceRotation::RIGHT,
+    TetrisPieceRotation::TWO,
+    TetrisPieceRotation::LEFT,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+    // garbage
+    G,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// Where a piece ends up, (`x`, `y`) being its center with `y` counted up
+/// from the bottom row.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub piece: PlayableTetrisPieceType,
+    pub orientation: Orientation,
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Start {
+    pub hold: Option<PlayableTetrisPieceType>,
+    // the piece in play first
+    pub queue: Vec<PlayableTetrisPieceType>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    pub board: Vec<Vec<Option<Cell>>>,
+}
+
+/// Messages from the game to the bot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start(Start),
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: PlayableTetrisPieceType,
+    },
+    Quit,
+}
+
+/// Messages from the bot to the game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Error {
+        reason: String,
+    },
+    Ready,
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+fn cell_of(piece: PlayableTetrisPieceType) -> Cell {
+    match piece {
+        PlayableTetrisPieceType::I => Cell::I,
+        PlayableTetrisPieceType::O => Cell::O,
+        PlayableTetrisPieceType::T => Cell::T,
+        PlayableTetrisPieceType::L => Cell::L,
+        PlayableTetrisPieceType::J => Cell::J,
+        PlayableTetrisPieceType::S => Cell::S,
+        PlayableTetrisPieceType::Z => Cell::Z,
+    }
+}
+
+fn piece_of(cell: Cell) -> TetrisPieceType {
+    let piece = match cell {
+        Cell::I => PlayableTetrisPieceType::I,
+        Cell::O => PlayableTetrisPieceType::O,
+        Cell::T => PlayableTetrisPieceType::T,
+        Cell::L => PlayableTetrisPieceType::L,
+        Cell::J => PlayableTetrisPieceType::J,
+        Cell::S => PlayableTetrisPieceType::S,
+        Cell::Z => PlayableTetrisPieceType::Z,
+        Cell::G => return TetrisPieceType::NotPlayable,
+    };
+    TetrisPieceType::Playable(piece)
+}
+
+/// `board` as sent by the protocol, rows above the board being empty.
+pub fn board_cells(board: &TetrisBoard) -> Vec<Vec<Option<Cell>>> {
+    (0..BOARD_ROWS as isize)
+        .map(|y| {
+            (0..board.cols)
+                .map(|x| {
+                    let i = board.rows - 1 - y;
+                    if !board.is_in_bounds(i, x) {
+                        return None;
+                    }
+                    match board.get(i, x) {
+                        TetrisCell::FilledCell(TetrisPieceType::Playable(p)) => Some(cell_of(p)),
+                        TetrisCell::FilledCell(TetrisPieceType::NotPlayable) => Some(Cell::G),
+                        TetrisCell::EmptyCell => None,
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Puts `cells` on an empty board of `rows` and `cols`, dropping what does
+/// not fit.
+pub fn board_from_cells(cells: &[Vec<Option<Cell>>], rows: isize, cols: isize) -> TetrisBoard {
+    let mut board = TetrisBoard::new(rows, cols);
+    for (y, row) in cells.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let (i, j) = (rows - 1 - y as isize, x as isize);
+            if let (Some(cell), true) = (cell, board.is_in_bounds(i, j)) {
+                board.set(i, j, piece_of(*cell));
+            }
+        }
+    }
+    board
+}
+
+// cells around the center facing north, with `y` going up
+fn north_cells(piece: PlayableTetrisPieceType) -> [(isize, isize); 4] {
+    match piece {
+        PlayableTetrisPieceType::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        PlayableTetrisPieceType::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        PlayableTetrisPieceType::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        PlayableTetrisPieceType::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        PlayableTetrisPieceType::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        PlayableTetrisPieceType::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        PlayableTetrisPieceType::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    }
+}
+
+// board (row, col) cells of `location`, sorted
+fn location_cells(location: &PieceLocation, rows: isize) -> Vec<(isize, isize)> {
+    let turns = ORIENTATIONS
+        .iter()
+        .position(|&o| o == location.orientation)
+        .unwrap();
+    let mut cells: Vec<_> = north_cells(location.piece)
+        .iter()
+        .map(|&(x, y)| (0..turns).fold((x, y), |(x, y), _| (y, -x)))
+        .map(|(x, y)| (rows - 1 - (location.y + y), location.x + x))
+        .collect();
+    cells.sort_unstable();
+    cells
+}
+
+// cells of the piece in its rotation box, sorted
+fn box_cells(
+    piece: PlayableTetrisPieceType,
+    rotation: TetrisPieceRotation,
+    rotation_system: &'static dyn RotationSystem,
+) -> Vec<(isize, isize)> {
+    let mut piece = TetrisPiece::with_rotation_system(piece, rotation_system);
+    piece.set_rotation(rotation);
+    let mut cells: Vec<_> = piece.set_cells().collect();
+    cells.sort_unstable();
+    cells
+}
+
+// the same orientation first, as spins depend on it
+fn rotations_from(index: usize) -> impl Iterator<Item = usize> {
+    (0..4).map(move |turn| (index + turn) % 4)
+}
+
+/// The placement of `rotation_system` covering the cells of `location` on a
+/// board of `rows`, or None if no rotation has that shape.
+pub fn placement_of(
+    location: &PieceLocation,
+    rotation_system: &'static dyn RotationSystem,
+    rows: isize,
+) -> Option<Placement> {
+    let target = location_cells(location, rows);
+    let orientation = ORIENTATIONS
+        .iter()
+        .position(|&o| o == location.orientation)
+        .unwrap();
+
+    rotations_from(orientation).find_map(|index| {
+        let rotation = ROTATIONS[index];
+        let cells = box_cells(location.piece, rotation, rotation_system);
+        let (row, col) = (target[0].0 - cells[0].0, target[0].1 - cells[0].1);
+        let fits = cells
+            .iter()
+            .zip(&target)
+            .all(|(&(i, j), &cell)| (i + row, j + col) == cell);
+        fits.then_some(Placement {
+            piece: location.piece,
+            rotation,
+            row,
+            col,
+        })
+    })
+}
+
+/// The protocol location of `placement` on a board of `rows`.
+pub fn location_of(
+    placement: &Placement,
+    rotation_system: &'static dyn RotationSystem,
+    rows: isize,
+) -> Option<PieceLocation> {
+    let mut target: Vec<_> = box_cells(placement.piece, placement.rotation, rotation_system)
+        .iter()
+        .map(|&(i, j)| (i + placement.row, j + placement.col))
+        .collect();
+    target.sort_unstable();
+    let rotation = ROTATIONS
+        .iter()
+        .position(|&r| r == placement.rotation)
+        .unwrap();
+
+    rotations_from(rotation).find_map(|index| {
+        let mut location = PieceLocation {
+            piece: placement.piece,
+            orientation: ORIENTATIONS[index],
+            x: 0,
+            y: 0,
+        };
+        let cells = location_cells(&location, rows);
+        location.x = target[0].1 - cells[0].1;
+        location.y = cells[0].0 - target[0].0;
+        Some(location).filter(|location| location_cells(location, rows) == target)
+    })
+}
+
+/// Locks `placement` on `board` and removes the completed rows, returns
+/// their number.
+pub fn lock_placement(
+    board: &mut TetrisBoard,
+    placement: &Placement,
+    rotation_system: &'static dyn RotationSystem,
+) -> u32 {
+    let mut piece = TetrisPiece::with_rotation_system(placement.piece, rotation_system);
+    piece.set_rotation(placement.rotation);
+    TetrisPieceWithPosition::new(placement.row, placement.col, piece).finalize_on(board);
+
+    let ranges = board.completed_rows();
+    let lines = ranges.iter().map(|r| (r.0 - r.1) as u32).sum();
+    board.remove_ranges(ranges);
+    lines
+}
+
+/// Queue and hold of a bot once `piece` is played, swapping it with the hold
+/// if it is not the first of the queue.
+pub fn after_play(
+    hold: &mut Option<PlayableTetrisPieceType>,
+    queue: &mut Vec<PlayableTetrisPieceType>,
+    piece: PlayableTetrisPieceType,
+) {
+    if queue.is_empty() {
+        return;
+    }
+    let current = queue.remove(0);
+    if current == piece {
+        return;
+    }
+    if hold.replace(current).is_none() && !queue.is_empty() {
+        queue.remove(0);
+    }
+}
+
+/// Serves our bot over the protocol.
+pub struct TbpBot {
+    bot: Bot,
+    rows: isize,
+    cols: isize,
+    spawn: (isize, isize),
+    rotation_system: &'static dyn RotationSystem,
+    kicks_180: Kicks180Type,
+    spin_rule: SpinRule,
+    board: TetrisBoard,
+    hold: Option<PlayableTetrisPieceType>,
+    // the piece in play first, empty when not playing
+    queue: Vec<PlayableTetrisPieceType>,
+    combo: u32,
+    b2b: u32,
+}
+
+impl TbpBot {
+    pub fn new(params: BotParams, rules: &GameParams) -> Self {
+        let game = Game::new(rules.clone());
+        TbpBot {
+            bot: Bot::new(params, rules),
+            rows: game.board().rows,
+            cols: game.board().cols,
+            spawn: game.spawn_position(),
+            rotation_system: rules.rotation_system.system(),
+            kicks_180: rules.kicks_180,
+            spin_rule: rules.spin_rule,
+            board: game.board().clone(),
+            hold: None,
+            queue: vec![],
+            combo: 0,
+            b2b: 0,
+        }
+    }
+
+    pub fn info(&self) -> BotMessage {
+        BotMessage::Info {
+            name: String::from("rust-tetris"),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            author: String::from("rust-tetris"),
+            features: vec![],
+        }
+    }
+
+    /// The reply to `message`, if it needs one.
+    pub fn handle(&mut self, message: FrontendMessage) -> Option<BotMessage> {
+        match message {
+            FrontendMessage::Rules => return Some(BotMessage::Ready),
+            FrontendMessage::Start(start) => {
+                self.board = board_from_cells(&start.board, self.rows, self.cols);
+                self.hold = start.hold;
+                self.queue = start.queue;
+                self.combo = start.combo;
+                self.b2b = start.back_to_back as u32;
+            }
+            FrontendMessage::Stop | FrontendMessage::Quit => self.queue.clear(),
+            FrontendMessage::Suggest => {
+                return Some(BotMessage::Suggestion {
+                    moves: self.suggest().into_iter().collect(),
+                })
+            }
+            FrontendMessage::Play { mv } => {
+                if let Err(reason) = self.play(&mv) {
+                    // the bot lost track of the game, it waits for a new start
+                    self.queue.clear();
+                    return Some(BotMessage::Error { reason });
+                }
+            }
+            FrontendMessage::NewPiece { piece } => self.queue.push(piece),
+        }
+        None
+    }
+
+    fn spawned(&self, piece: PlayableTetrisPieceType) -> TetrisPieceWithPosition {
+        let piece = TetrisPiece::with_rotation_system(piece, self.rotation_system)
+            .with_kicks_180(self.kicks_180);
+        TetrisPieceWithPosition::new(self.spawn.0, self.spawn.1, piece)
+    }
+
+    fn state(&self) -> Option<BotState> {
+        let (&current, queue) = self.queue.split_first()?;
+        Some(BotState {
+            board: self.board.clone(),
+            piece: self.spawned(current),
+            hold: self.hold,
+            can_hold: true,
+            queue: queue.to_vec(),
+            spawn: self.spawn,
+            b2b: self.b2b,
+            combo: self.combo,
+        })
+    }
+
+    fn suggest(&self) -> Option<Move> {
+        let bot_move = self.bot.think(&self.state()?)?;
+        let location = location_of(&bot_move.placement, self.rotation_system, self.rows)?;
+        Some(Move {
+            location,
+            spin: self.spin_of(&bot_move.placement),
+        })
+    }
+
+    // the spin of `placement` as the search found it
+    fn spin_of(&self, placement: &Placement) -> Spin {
+        let piece = self.spawned(placement.piece);
+        let generated = generate_moves(&self.board, &piece, &PlannerOptions::default())
+            .into_iter()
+            .find(|generated| generated.placement() == *placement);
+        match generated.map(|generated| generated.spin(&self.board, self.spin_rule)) {
+            Some(SpinType::Full) => Spin::Full,
+            Some(SpinType::Mini) => Spin::Mini,
+            Some(SpinType::None) | None => Spin::None,
+        }
+    }
+
+    fn play(&mut self, mv: &Move) -> Result<(), String> {
+        let placement = placement_of(&mv.location, self.rotation_system, self.rows)
+            .ok_or_else(|| format!("invalid location {:?}", mv.location))?;
+        let in_play = match (self.queue.as_slice(), self.hold) {
+            ([current, ..], _) if *current == placement.piece => true,
+            ([_, ..], Some(hold)) => hold == placement.piece,
+            ([_, next, ..], None) => *next == placement.piece,
+            _ => false,
+        };
+        if !in_play {
+            return Err(format!("{:?} is not in play", placement.piece));
+        }
+        let mut piece = TetrisPiece::with_rotation_system(placement.piece, self.rotation_system);
+        piece.set_rotation(placement.rotation);
+        if TetrisPieceWithPosition::new(placement.row, placement.col, piece).collides(&self.board) {
+            return Err(format!("blocked location {:?}", mv.location));
+        }
+
+        let lines = lock_placement(&mut self.board, &placement, self.rotation_system);
+        after_play(&mut self.hold, &mut self.queue, mv.location.piece);
+
+        if lines == 0 {
+            self.combo = 0;
+        } else {
+            self.combo += 1;
+            self.b2b = match (lines, mv.spin) {
+                (4, _) | (_, Spin::Mini) | (_, Spin::Full) => self.b2b + 1,
+                _ => 0,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation::RotationSystemType;
+
+    fn srs() -> &'static dyn RotationSystem {
+        RotationSystemType::Srs.system()
+    }
+
+    #[test]
+    fn test_locations_round_trip() {
+        let pieces = [
+            PlayableTetrisPieceType::I,
+            PlayableTetrisPieceType::O,
+            PlayableTetrisPieceType::T,
+            PlayableTetrisPieceType::S,
+            PlayableTetrisPieceType::Z,
+            PlayableTetrisPieceType::L,
+            PlayableTetrisPieceType::J,
+        ];
+        for &piece in &pieces {
+            for &orientation in &ORIENTATIONS {
+                let location = PieceLocation {
+                    piece,
+                    orientation,
+                    x: 4,
+                    y: 10,
+                };
+                let placement = placement_of(&location, srs(), 40).unwrap();
+                assert_eq!(location_of(&placement, srs(), 40), Some(location));
+            }
+        }
+    }
+
+    #[test]
+    fn test_t_north_on_the_floor() {
+        let location = PieceLocation {
+            piece: PlayableTetrisPieceType::T,
+            orientation: Orientation::North,
+            x: 4,
+            y: 0,
+        };
+        let placement = placement_of(&location, srs(), 40).unwrap();
+        let mut board = TetrisBoard::new(40, 10);
+
+        lock_placement(&mut board, &placement, srs());
+
+        assert_eq!(board.row_bits(39), 0b111 << 3);
+        assert_eq!(board.row_bits(38), 0b1 << 4);
+        let cells = board_cells(&board);
+        assert_eq!(cells[0][3], Some(Cell::T));
+        assert_eq!(cells[1][4], Some(Cell::T));
+        assert_eq!(cells[1][3], None);
+    }
+
+    #[test]
+    fn test_after_play_with_hold() {
+        use PlayableTetrisPieceType::*;
+
+        let (mut hold, mut queue) = (None, vec![T, I, O]);
+        after_play(&mut hold, &mut queue, I);
+        assert_eq!((hold, queue.clone()), (Some(T), vec![O]));
+
+        after_play(&mut hold, &mut queue, T);
+        assert_eq!((hold, queue.clone()), (Some(O), vec![]));
+    }
+
+    #[test]
+    fn test_bot_suggests_a_move() {
+        let mut bot = TbpBot::new(BotParams::default(), &GameParams::default());
+        let empty = board_cells(&TetrisBoard::new(40, 10));
+
+        assert_eq!(bot.handle(FrontendMessage::Rules), Some(BotMessage::Ready));
+        let start = Start {
+            hold: None,
+            queue: vec![PlayableTetrisPieceType::I, PlayableTetrisPieceType::O],
+            combo: 0,
+            back_to_back: false,
+            board: empty,
+        };
+        assert_eq!(bot.handle(FrontendMessage::Start(start)), None);
+
+        let moves = match bot.handle(FrontendMessage::Suggest) {
+            Some(BotMessage::Suggestion { moves }) => moves,
+            other => panic!("Unexpected reply: {:?}", other),
+        };
+        assert_eq!(moves.len(), 1);
+
+        bot.handle(FrontendMessage::Play { mv: moves[0] });
+        let cells: u32 = (0..40).map(|i| bot.board.row_bits(i).count_ones()).sum();
+        assert_eq!(cells, 4);
+        assert_eq!(bot.queue.len(), 1);
+    }
+
+    #[test]
+    fn test_bot_suggests_a_tsd() {
+        let mut bot = TbpBot::new(BotParams::default(), &GameParams::default());
+        let mut board = TetrisBoard::new(40, 10);
+        let rows = ["**        ", "*   ******", "** *******"];
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                if c == '*' {
+                    board.set(37 + i as isize, j as isize, TetrisPieceType::NotPlayable);
+                }
+            }
+        }
+        let start = Start {
+            hold: None,
+            queue: vec![PlayableTetrisPieceType::T],
+            combo: 0,
+            back_to_back: false,
+            board: board_cells(&board),
+        };
+        bot.handle(FrontendMessage::Start(start));
+
+        let mv = match bot.handle(FrontendMessage::Suggest) {
+            Some(BotMessage::Suggestion { moves }) => moves[0],
+            other => panic!("Unexpected reply: {:?}", other),
+        };
+        assert_eq!(mv.location.orientation, Orientation::South);
+        assert_eq!(mv.spin, Spin::Full);
+
+        bot.handle(FrontendMessage::Play { mv });
+        assert_eq!(bot.b2b, 1);
+    }
+
+    #[test]
+    fn test_bot_rejects_an_invalid_move() {
+        let mut bot = TbpBot::new(BotParams::default(), &GameParams::default());
+        let start = |piece| Start {
+            hold: None,
+            queue: vec![piece],
+            combo: 0,
+            back_to_back: false,
+            board: board_cells(&TetrisBoard::new(40, 10)),
+        };
+        let mv = |x, y| Move {
+            location: PieceLocation {
+                piece: PlayableTetrisPieceType::O,
+                orientation: Orientation::North,
+                x,
+                y,
+            },
+            spin: Spin::None,
+        };
+
+        bot.handle(FrontendMessage::Start(start(PlayableTetrisPieceType::O)));
+        let reply = bot.handle(FrontendMessage::Play { mv: mv(9, 0) });
+        assert!(matches!(reply, Some(BotMessage::Error { .. })));
+        assert!(bot.queue.is_empty());
+        assert!(bot.board.is_empty());
+
+        bot.handle(FrontendMessage::Start(start(PlayableTetrisPieceType::I)));
+        let reply = bot.handle(FrontendMessage::Play { mv: mv(4, 0) });
+        assert!(matches!(reply, Some(BotMessage::Error { .. })));
+        assert!(bot.queue.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+//! Our bot behind the Tetris Bot Protocol, to try the TBP frontend of the
+//! game without a third-party bot: `cargo run -- --tbp target/debug/tbp_bot`
+//!
+//! It does not read the game config and plans with the default rules: SRS
+//! and the default board height.
+
+use std::io::{self, BufRead, Write};
+
+use rust_tetris_core::{
+    bot::BotParams,
+    engine_structs::GameParams,
+    tbp::{BotMessage, FrontendMessage, TbpBot},
+};
+
+fn send(out: &mut impl Write, message: &BotMessage) -> io::Result<()> {
+    writeln!(out, "{}", serde_json::to_string(message)?)?;
+    out.flush()
+}
+
+fn main() -> io::Result<()> {
+    let mut bot = TbpBot::new(BotParams::default(), &GameParams::default());
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    send(&mut out, &bot.info())?;
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let message: FrontendMessage = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Ignoring message {:?}: {}", line, e);
+                continue;
+            }
+        };
+        if message == FrontendMessage::Quit {
+            break;
+        }
+        if let Some(reply) = bot.handle(message) {
+            send(&mut out, &reply)?;
+        }
+    }
+    Ok(())
+}
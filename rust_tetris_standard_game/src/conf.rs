@@ -1,4 +1,5 @@
 use ggez::{event::Button, input::keyboard::KeyCode};
+use rust_tetris_core::{bot::BotParams, engine_structs::GameParams};
 use serde_derive::Deserialize;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -12,28 +13,28 @@ pub trait Validable {
 
 #[derive(Deserialize, Debug)]
 pub struct GameConfig {
-    pub game_params: GameParamsConfig,
+    pub game_params: GameParams,
     pub keys: KeysConfig,
+    #[serde(default = "default_replay_dir")]
+    pub replay_dir: String,
+    #[serde(default)]
+    pub bot: BotParams,
+}
+
+fn default_replay_dir() -> String {
+    String::from("replays")
 }
 
 impl Validable for GameConfig {
     fn validate(&self) -> ValidationResult {
         self.game_params.validate()?;
         self.keys.validate()?;
+        self.bot.validate()?;
         Ok(())
     }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct GameParamsConfig {
-    pub gravity: f64,
-    pub soft_drop_factor: f64,
-    pub das: f64,
-    pub arr: f64,
-    pub lock_delay: u32,
-}
-
-impl Validable for GameParamsConfig {
+impl Validable for GameParams {
     fn validate(&self) -> ValidationResult {
         let mut errs = vec![];
         if self.gravity <= 0.0 {
@@ -42,16 +43,43 @@ impl Validable for GameParamsConfig {
         if self.soft_drop_factor <= 0.0 {
             errs.push(Err(String::from("invalid soft_drop_factor")));
         }
-        if self.das <= 0.0 {
+        if self.das < 0.0 {
             errs.push(Err(String::from("invalid das")));
         }
-        if self.arr <= 0.0 {
+        if self.arr < 0.0 {
             errs.push(Err(String::from("invalid arr")));
         }
+        if self.dcd < 0.0 {
+            errs.push(Err(String::from("invalid dcd")));
+        }
+        if !(0.0..=1.0).contains(&self.garbage.messiness) {
+            errs.push(Err(String::from("invalid garbage messiness")));
+        }
+        if self.tick_rate == 0 {
+            errs.push(Err(String::from("invalid tick_rate")));
+        }
+        if self.start_level == 0 {
+            errs.push(Err(String::from("invalid start_level")));
+        }
+        if !(4..=64).contains(&self.width) {
+            errs.push(Err(String::from("invalid width")));
+        }
+        if self.visible_height == 0 {
+            errs.push(Err(String::from("invalid visible_height")));
+        }
         join_results_array(errs)
     }
 }
 
+impl Validable for BotParams {
+    fn validate(&self) -> ValidationResult {
+        if self.beam_width == 0 {
+            return Err(String::from("invalid bot beam_width"));
+        }
+        Ok(())
+    }
+}
+
 type ConfigWithName<'a, 'b> = (&'a str, &'b KeyConfig);
 
 #[derive(Deserialize, Debug)]
@@ -60,16 +88,24 @@ pub struct KeysConfig {
     pub right: KeyConfig,
     pub next_rotation: KeyConfig,
     pub prev_rotation: KeyConfig,
+    #[serde(default)]
+    pub rotate_180: Option<KeyConfig>,
     pub pause: KeyConfig,
     pub soft_drop: KeyConfig,
     pub hard_drop: KeyConfig,
+    #[serde(default)]
+    pub sonic_drop: Option<KeyConfig>,
     pub hold: KeyConfig,
     pub quit: KeyConfig,
+    #[serde(default)]
+    pub undo: Option<KeyConfig>,
+    #[serde(default)]
+    pub redo: Option<KeyConfig>,
 }
 
 impl Validable for KeysConfig {
     fn validate(&self) -> ValidationResult {
-        let children = vec![
+        let mut children = vec![
             ("left", &self.left),
             ("right", &self.right),
             ("next_rotation", &self.next_rotation),
@@ -81,6 +117,19 @@ impl Validable for KeysConfig {
             ("quit", &self.quit),
         ];
 
+        if let Some(rotate_180) = self.rotate_180.as_ref() {
+            children.push(("rotate_180", rotate_180));
+        }
+        if let Some(sonic_drop) = self.sonic_drop.as_ref() {
+            children.push(("sonic_drop", sonic_drop));
+        }
+        if let Some(undo) = self.undo.as_ref() {
+            children.push(("undo", undo));
+        }
+        if let Some(redo) = self.redo.as_ref() {
+            children.push(("redo", redo));
+        }
+
         let mut results = vec![];
 
         for (name, child) in &children {
@@ -182,6 +231,7 @@ fn get_duplicated_entries<'b, 'c, T: Hash + Eq, F: Fn(&KeyConfig) -> &Option<Vec
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_tetris_core::attack::AttackTableType;
 
     fn get_valid_keyconfig_just_kb() -> KeyConfig {
         KeyConfig {
@@ -275,25 +325,133 @@ mod tests {
 
     #[test]
     fn test_valid_gameparams() {
-        let conf = GameParamsConfig {
+        let conf = GameParams {
             arr: 1.0,
             das: 1.0,
             gravity: 1.0,
             lock_delay: 1,
             soft_drop_factor: 1.0,
+            ..GameParams::default()
         };
         let res = conf.validate();
         assert!(res.is_ok(), "Invalid configuration: {:?}", res);
     }
 
+    #[test]
+    fn test_zero_arr_is_valid() {
+        let conf = GameParams {
+            arr: 0.0,
+            dcd: 0.0,
+            ..GameParams::default()
+        };
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_tick_rate() {
+        let conf = GameParams {
+            tick_rate: 0,
+            ..GameParams::default()
+        };
+        assert_eq!(conf.validate().err().unwrap(), "invalid tick_rate");
+    }
+
+    #[test]
+    fn test_invalid_board_dimensions() {
+        let conf = GameParams {
+            width: 3,
+            visible_height: 0,
+            ..GameParams::default()
+        };
+        let res = conf.validate();
+        assert_eq!(res.err().unwrap(), "invalid width;invalid visible_height");
+    }
+
+    #[test]
+    fn test_infinite_soft_drop_factor() {
+        let conf: GameParams = toml::from_str(
+            r#"
+            gravity = 0.016
+            soft_drop_factor = inf
+            das = 10.0
+            arr = 2.0
+            lock_delay = 30
+            "#,
+        )
+        .unwrap();
+
+        assert!(conf.soft_drop_factor.is_infinite());
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn test_custom_attack_table() {
+        let conf: GameParams = toml::from_str(
+            r#"
+            gravity = 0.016
+            soft_drop_factor = 40.0
+            das = 10.0
+            arr = 2.0
+            lock_delay = 30
+
+            [attack_table]
+            type = "Custom"
+            single = 1
+            double = 2
+            triple = 3
+            tetris = 5
+            t_spin_mini_single = 1
+            t_spin_mini_double = 2
+            t_spin_single = 3
+            t_spin_double = 5
+            t_spin_triple = 7
+            back_to_back = 2
+            combo = [0, 1, 2]
+            perfect_clear = 6
+            "#,
+        )
+        .unwrap();
+
+        match conf.attack_table {
+            AttackTableType::Custom(table) => {
+                assert_eq!(table.tetris, 5);
+                assert_eq!(table.combo, vec![0, 1, 2]);
+            }
+            other => panic!("Unexpected attack table: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_partial_bot_params() {
+        let conf: BotParams = toml::from_str(
+            r#"
+            lookahead = 4
+            beam_width = 0
+
+            [weights]
+            holes = -8.0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(conf.lookahead, 4);
+        assert_eq!(conf.weights.holes, -8.0);
+        assert_eq!(
+            conf.weights.bumpiness,
+            BotParams::default().weights.bumpiness
+        );
+        assert_eq!(conf.validate().err().unwrap(), "invalid bot beam_width");
+    }
+
     #[test]
     fn test_invalid_gameparams() {
-        let conf = GameParamsConfig {
+        let conf = GameParams {
             arr: -1.0,
             das: -1.0,
             gravity: -1.0,
             lock_delay: 1,
             soft_drop_factor: -1.0,
+            ..GameParams::default()
         };
         let res = conf.validate();
         assert!(res.is_err());
@@ -5,12 +5,14 @@ use ggez::{
     input::{gamepad::GamepadId, keyboard::KeyMods},
     Context, GameResult,
 };
-use log::debug;
-use rust_tetris_core::enums::PlayableTetrisPieceType;
+use log::{debug, info};
+use rust_tetris_core::{
+    engine_structs::{GameMode, TetrisUpdateResult},
+    enums::PlayableTetrisPieceType,
+};
 use std::{collections::HashMap, rc::Rc};
 
 use crate::app::App;
-use crate::types::TetrisUpdateResult;
 
 type KeysMap = HashMap<CodeWithState, ControllerKey>;
 type GamePadMap = HashMap<Button, ControllerKey>;
@@ -23,7 +25,7 @@ fn read_key(
     gamepad_map: &mut GamePadMap,
 ) {
     fn vec_or_empty<T>(o: &Option<Vec<T>>) -> impl Iterator<Item = &T> {
-        o.as_ref().into_iter().map(|v| v.iter()).flatten()
+        o.as_ref().into_iter().flat_map(|v| v.iter())
     }
 
     for &k in vec_or_empty(&key_config.keyboard) {
@@ -35,7 +37,7 @@ fn read_key(
     }
 }
 
-fn register_debug_keys(keys_map: &mut KeysMap, gamepad_map: &mut GamePadMap) {
+fn register_debug_keys(height: usize, keys_map: &mut KeysMap, gamepad_map: &mut GamePadMap) {
     fn deserialize_key_code<S: AsRef<str>>(s: S) -> KeyConfig {
         KeyConfig {
             keyboard: Some(vec![serde_plain::from_str::<KeyCode>(s.as_ref())
@@ -96,25 +98,40 @@ fn register_debug_keys(keys_map: &mut KeysMap, gamepad_map: &mut GamePadMap) {
     );
 
     for i in 0..=9 {
-        // ctrl + 1..9 -> 19..10, 0 -> 9
-        let l = if i > 0 { 20 - i } else { 9 };
-        read_key(
-            ControllerKey::RemoveLine(l),
-            &deserialize_key_code(format!("Key{}", i)),
-            KeyMods::CTRL,
-            keys_map,
-            gamepad_map,
-        );
+        // ctrl + 1..9 -> 1st..9th line from the bottom, 0 -> 10th
+        let n = if i > 0 { i } else { 10 };
+        if let Some(l) = height.checked_sub(n) {
+            read_key(
+                ControllerKey::RemoveLine(l),
+                &deserialize_key_code(format!("Key{}", i)),
+                KeyMods::CTRL,
+                keys_map,
+                gamepad_map,
+            );
+        }
 
-        // ctrl + shift + 1..9 -> 8..0
+        // ctrl + shift + 0..9 -> 10th..1st line from the top
         let m = 9 - i;
-        read_key(
-            ControllerKey::RemoveLine(m),
-            &deserialize_key_code(format!("Key{}", i)),
-            KeyMods::CTRL | KeyMods::SHIFT,
-            keys_map,
-            gamepad_map,
-        );
+        if m < height {
+            read_key(
+                ControllerKey::RemoveLine(m),
+                &deserialize_key_code(format!("Key{}", i)),
+                KeyMods::CTRL | KeyMods::SHIFT,
+                keys_map,
+                gamepad_map,
+            );
+        }
+
+        // alt + 1..9 -> receive 1..9 garbage lines
+        if i > 0 {
+            read_key(
+                ControllerKey::AddGarbage(i as u32),
+                &deserialize_key_code(format!("Key{}", i)),
+                KeyMods::ALT,
+                keys_map,
+                gamepad_map,
+            );
+        }
     }
 }
 
@@ -150,6 +167,15 @@ fn create_keys_map(config: &Rc<GameConfig>) -> (KeysMap, GamePadMap) {
         &mut keys_map,
         &mut gamepad_map,
     );
+    if let Some(rotate_180) = config.keys.rotate_180.as_ref() {
+        read_key(
+            ControllerKey::Rotate180,
+            rotate_180,
+            KeyMods::default(),
+            &mut keys_map,
+            &mut gamepad_map,
+        );
+    }
     read_key(
         ControllerKey::Pause,
         &config.keys.pause,
@@ -171,6 +197,15 @@ fn create_keys_map(config: &Rc<GameConfig>) -> (KeysMap, GamePadMap) {
         &mut keys_map,
         &mut gamepad_map,
     );
+    if let Some(sonic_drop) = config.keys.sonic_drop.as_ref() {
+        read_key(
+            ControllerKey::SonicDrop,
+            sonic_drop,
+            KeyMods::default(),
+            &mut keys_map,
+            &mut gamepad_map,
+        );
+    }
     read_key(
         ControllerKey::Hold,
         &config.keys.hold,
@@ -185,9 +220,36 @@ fn create_keys_map(config: &Rc<GameConfig>) -> (KeysMap, GamePadMap) {
         &mut keys_map,
         &mut gamepad_map,
     );
+    if let Some(undo) = config.keys.undo.as_ref() {
+        read_key(
+            ControllerKey::Undo,
+            undo,
+            KeyMods::default(),
+            &mut keys_map,
+            &mut gamepad_map,
+        );
+    }
+    if let Some(redo) = config.keys.redo.as_ref() {
+        read_key(
+            ControllerKey::Redo,
+            redo,
+            KeyMods::default(),
+            &mut keys_map,
+            &mut gamepad_map,
+        );
+    }
 
-    if cfg!(debug_assertions) {
-        register_debug_keys(&mut keys_map, &mut gamepad_map);
+    let debug_keys = match config.game_params.mode {
+        GameMode::Practice => cfg!(debug_assertions),
+        GameMode::Sandbox => true,
+        GameMode::Ranked => false,
+    };
+    if debug_keys {
+        register_debug_keys(
+            config.game_params.visible_height,
+            &mut keys_map,
+            &mut gamepad_map,
+        );
     }
 
     (keys_map, gamepad_map)
@@ -218,7 +280,7 @@ impl Controller {
     }
 
     fn exec_if_not_paused<F: FnMut(&mut App)>(&mut self, mut ex: F) {
-        if !self.app.is_paused() {
+        if !self.app.is_paused() && !self.app.is_game_over() {
             ex(&mut self.app);
         }
     }
@@ -250,8 +312,26 @@ impl Controller {
         self.on_press(ctx, ctrl_key);
     }
 
+    fn on_replay_press(&mut self, ctx: &mut Context, ctrl_key: Option<ControllerKey>) {
+        match ctrl_key {
+            Some(ControllerKey::Pause) if self.app.is_game_over() => self.app.restart(),
+            Some(ControllerKey::Pause) => self.app.toggle_pause(),
+            Some(ControllerKey::Left) => self.app.seek_replay(false),
+            Some(ControllerKey::Right) => self.app.seek_replay(true),
+            Some(ControllerKey::HardDrop) => self.app.change_replay_speed(true),
+            Some(ControllerKey::SoftDrop) => self.app.change_replay_speed(false),
+            Some(ControllerKey::Quit) => event::quit(ctx),
+            _ => {}
+        }
+    }
+
     fn on_press(&mut self, ctx: &mut Context, ctrl_key: Option<ControllerKey>) {
+        if self.app.is_replay() {
+            return self.on_replay_press(ctx, ctrl_key);
+        }
+
         match ctrl_key {
+            Some(ControllerKey::Pause) if self.app.is_game_over() => self.app.restart(),
             Some(ControllerKey::Pause) => self.app.toggle_pause(),
             Some(ControllerKey::Left) => self.exec_if_not_paused(|app| app.left_key_pressed()),
             Some(ControllerKey::Right) => self.exec_if_not_paused(|app| app.right_key_pressed()),
@@ -261,25 +341,45 @@ impl Controller {
             Some(ControllerKey::PrevRotation) => {
                 self.exec_if_not_paused(|app| app.prev_rot_pressed())
             }
+            Some(ControllerKey::Rotate180) => self.exec_if_not_paused(|app| app.rot_180_pressed()),
             Some(ControllerKey::SoftDrop) => {
                 self.exec_if_not_paused(|app| app.soft_drop_key_pressed())
             }
             Some(ControllerKey::HardDrop) => {
                 self.exec_if_not_paused(|app| app.hard_drop_key_pressed())
             }
+            Some(ControllerKey::SonicDrop) => {
+                self.exec_if_not_paused(|app| app.sonic_drop_key_pressed())
+            }
             Some(ControllerKey::Hold) => self.exec_if_not_paused(|app| app.hold_key_pressed()),
-            Some(ControllerKey::Quit) => event::quit(ctx),
+            Some(ControllerKey::Quit) => {
+                self.app.save_replay();
+                event::quit(ctx)
+            }
+            // undo also gets out of a game over
+            Some(ControllerKey::Undo) if !self.app.is_paused() => self.app.undo(),
+            Some(ControllerKey::Redo) if !self.app.is_paused() => self.app.redo(),
             Some(ControllerKey::RemoveLine(n)) => self.app.remove_line(n),
             Some(ControllerKey::Choose(p)) => self.app.set_current(p),
+            Some(ControllerKey::AddGarbage(lines)) => self.app.receive_garbage(lines),
             _ => {}
         }
     }
 
     fn on_release(&mut self, ctrl_key: Option<ControllerKey>) {
+        if self.app.is_replay() {
+            return;
+        }
+
         match ctrl_key {
             Some(ControllerKey::Left) => self.app.left_key_released(),
             Some(ControllerKey::Right) => self.app.right_key_released(),
             Some(ControllerKey::SoftDrop) => self.app.soft_drop_key_released(),
+            // rotation and hold releases matter for IRS and IHS
+            Some(ControllerKey::NextRotation) => self.app.next_rot_released(),
+            Some(ControllerKey::PrevRotation) => self.app.prev_rot_released(),
+            Some(ControllerKey::Rotate180) => self.app.rot_180_released(),
+            Some(ControllerKey::Hold) => self.app.hold_key_released(),
             _ => {}
         }
     }
@@ -287,12 +387,10 @@ impl Controller {
 
 impl EventHandler for Controller {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
-        match self.app.update(ctx) {
-            Ok(TetrisUpdateResult::GameOver) => {
-                panic!("Game over");
-            }
-            other => other.map(|_| ()),
+        if let TetrisUpdateResult::GameOver { reason } = self.app.update(ctx)? {
+            info!("Game over: {:?}", reason);
         }
+        Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
@@ -312,8 +410,12 @@ impl EventHandler for Controller {
         ctx: &mut Context,
         keycode: KeyCode,
         keymods: KeyMods,
-        _repeat: bool,
+        repeat: bool,
     ) {
+        // a held rotation key must not rotate again, replays still seek while a key is held
+        if repeat && !self.app.is_replay() {
+            return;
+        }
         self.handle_key(ctx, keycode, keymods);
     }
 
@@ -6,12 +6,16 @@ pub enum ControllerKey {
     Right,
     NextRotation,
     PrevRotation,
+    Rotate180,
     Pause,
     SoftDrop,
     HardDrop,
+    SonicDrop,
     Hold,
     Quit,
     Undo,
+    Redo,
     Choose(PlayableTetrisPieceType),
     RemoveLine(usize),
+    AddGarbage(u32),
 }
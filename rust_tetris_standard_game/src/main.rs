@@ -14,12 +14,14 @@ use ggez::{
 };
 use log::{debug, error, info, LevelFilter};
 
+use rust_tetris_core::bot::{Bot, BotPlayer};
 use rust_tetris_ui_core::utils::{WIN_H, WIN_W};
 
 use crate::{
     app::App,
     conf::{GameConfig, Validable},
     controller::Controller,
+    tbp_frontend::TbpFrontend,
 };
 
 #[macro_use]
@@ -27,7 +29,8 @@ mod app;
 mod conf;
 mod controller;
 mod controller_structs;
-mod types;
+mod replay_file;
+mod tbp_frontend;
 
 fn init_log() {
     env_logger::builder()
@@ -76,7 +79,33 @@ fn main() {
 
     let rc_config = Rc::new(config);
 
-    let app = App::new(font, Rc::clone(&rc_config));
+    // rust_tetris_standard_game --replay <file> plays a recorded game back,
+    // --bot lets the bot play and --tbp <command> [args] an external TBP bot
+    let args: Vec<String> = std::env::args().collect();
+    let replay = args.iter().position(|a| a == "--replay");
+    let tbp = args.iter().position(|a| a == "--tbp");
+    let app = if let Some(i) = replay {
+        let path = args.get(i + 1).expect("Missing replay file");
+        let replay = replay_file::load(path).expect("Replay load error");
+        replay.params.validate().unwrap();
+        App::with_replay(font, Rc::clone(&rc_config), replay).unwrap()
+    } else if let Some(i) = tbp {
+        if rc_config.game_params.width != 10 {
+            error!(
+                "TBP bots play on 10 columns, not {}",
+                rc_config.game_params.width
+            );
+            return;
+        }
+        let command = args.get(i + 1).expect("Missing bot command");
+        let bot = TbpFrontend::spawn(command, &args[i + 2..]).expect("Bot start error");
+        App::with_bot(font, Rc::clone(&rc_config), BotPlayer::new(bot))
+    } else if args.iter().any(|a| a == "--bot") {
+        let bot = Bot::new(rc_config.bot.clone(), &rc_config.game_params);
+        App::with_bot(font, Rc::clone(&rc_config), BotPlayer::new(bot))
+    } else {
+        App::new(font, Rc::clone(&rc_config))
+    };
     let mut controller = Controller::new(app, Rc::clone(&rc_config));
 
     controller.start();
@@ -0,0 +1,67 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rust_tetris_core::replay::Replay;
+
+pub fn to_toml(replay: &Replay) -> Result<String, String> {
+    // going through a Value puts the plain values before the tables, as TOML requires
+    toml::Value::try_from(replay)
+        .and_then(|value| toml::to_string_pretty(&value))
+        .map_err(|e| e.to_string())
+}
+
+pub fn from_toml(s: &str) -> Result<Replay, String> {
+    toml::from_str(s).map_err(|e| e.to_string())
+}
+
+pub fn new_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    dir.as_ref().join(format!("replay-{}.toml", secs))
+}
+
+pub fn save<P: AsRef<Path>>(replay: &Replay, path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, to_toml(replay)?).map_err(|e| e.to_string())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, String> {
+    let s = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    from_toml(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_tetris_core::{
+        engine::Game,
+        engine_structs::{GameInput, GameKey, GameParams},
+    };
+
+    #[test]
+    fn test_toml_round_trip() {
+        let mut game = Game::new(GameParams::default());
+        game.start();
+        game.tick(&[GameInput::Pressed(GameKey::Left)]);
+        game.receive_garbage(3);
+        game.tick(&[GameInput::Pressed(GameKey::HardDrop)]);
+
+        let replay = game.replay();
+        let s = to_toml(&replay).unwrap();
+        let loaded = from_toml(&s).unwrap();
+
+        assert_eq!(loaded.version, replay.version);
+        assert_eq!(loaded.frames, 2);
+        assert_eq!(loaded.params.seed, Some(game.seed()));
+        assert_eq!(loaded.params.attack_table, replay.params.attack_table);
+        assert_eq!(loaded.events, replay.events);
+    }
+}
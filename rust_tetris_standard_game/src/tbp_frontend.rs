@@ -0,0 +1,416 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    iter,
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use log::{error, info, warn};
+
+use rust_tetris_core::{
+    board::TetrisBoard,
+    bot::{BotMove, BotState, MoveSource},
+    engine::Game,
+    enums::PlayableTetrisPieceType,
+    planner::PlannerOptions,
+    rotation::RotationSystem,
+    tbp::{self, BotMessage, FrontendMessage, Move, Start},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Handshake {
+    // the bot introduces itself first
+    WaitingInfo,
+    WaitingReady,
+    Ready,
+    Failed,
+}
+
+// what the bot knows of the game
+struct BotView {
+    board: TetrisBoard,
+    hold: Option<PlayableTetrisPieceType>,
+    queue: Vec<PlayableTetrisPieceType>,
+}
+
+fn same_board(a: &TetrisBoard, b: &TetrisBoard) -> bool {
+    a.rows == b.rows && (0..a.rows).all(|i| a.row_bits(i) == b.row_bits(i))
+}
+
+/// Plays the moves suggested by an external bot speaking the Tetris Bot
+/// Protocol, one JSON message per line.
+pub struct TbpFrontend {
+    writer: Box<dyn Write>,
+    replies: Receiver<BotMessage>,
+    child: Option<Child>,
+    handshake: Handshake,
+    view: Option<BotView>,
+    // placed pieces count of the last suggestion asked
+    asked_at: Option<u64>,
+    // suggestions asked and not received yet, only the last one is used
+    outstanding: usize,
+    moves: Option<Vec<Move>>,
+    options: PlannerOptions,
+}
+
+impl TbpFrontend {
+    pub fn new(writer: Box<dyn Write>, replies: Receiver<BotMessage>) -> Self {
+        TbpFrontend {
+            writer,
+            replies,
+            child: None,
+            handshake: Handshake::WaitingInfo,
+            view: None,
+            asked_at: None,
+            outstanding: 0,
+            moves: None,
+            options: PlannerOptions::default(),
+        }
+    }
+
+    /// Starts the bot executable `command` with `args`.
+    pub fn spawn(command: &str, args: &[String]) -> io::Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || read_replies(stdout, sender));
+
+        let mut frontend = TbpFrontend::new(Box::new(stdin), replies);
+        frontend.child = Some(child);
+        Ok(frontend)
+    }
+
+    fn send(&mut self, message: &FrontendMessage) {
+        let sent = serde_json::to_string(message)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(self.writer, "{}", line).map_err(|e| e.to_string()))
+            .and_then(|_| self.writer.flush().map_err(|e| e.to_string()));
+        if let Err(e) = sent {
+            error!("Cannot send {:?} to the bot: {}", message, e);
+            self.handshake = Handshake::Failed;
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok(message) = self.replies.try_recv() {
+            match message {
+                BotMessage::Info {
+                    name,
+                    version,
+                    author,
+                    ..
+                } => {
+                    info!("Playing with {} {} by {}", name, version, author);
+                    if self.handshake == Handshake::WaitingInfo {
+                        self.handshake = Handshake::WaitingReady;
+                        self.send(&FrontendMessage::Rules);
+                    }
+                }
+                BotMessage::Ready => {
+                    if self.handshake == Handshake::WaitingReady {
+                        self.handshake = Handshake::Ready;
+                    }
+                }
+                BotMessage::Error { reason } if self.handshake == Handshake::Ready => {
+                    // the bot rejected a move, it is started again on the next piece
+                    warn!("The bot lost track of the game: {}", reason);
+                    MoveSource::reset(self);
+                }
+                BotMessage::Error { reason } => {
+                    error!("The bot refused the game: {}", reason);
+                    self.handshake = Handshake::Failed;
+                }
+                BotMessage::Suggestion { moves } => {
+                    self.outstanding = self.outstanding.saturating_sub(1);
+                    if self.outstanding == 0 {
+                        self.moves = Some(moves);
+                    }
+                }
+            }
+        }
+    }
+
+    // tells the bot about the pieces revealed since its last move, or
+    // starts it again when the game went another way
+    fn sync(&mut self, game: &Game) {
+        let board = game.board().clone();
+        let hold = game.hold_piece().map(|h| h.piece.piece_type);
+        let queue: Vec<_> = iter::once(game.piece().unwrap().tetris_piece_ref().piece_type)
+            .chain(game.next_pieces().map(|p| p.piece_type))
+            .collect();
+
+        let known = match &self.view {
+            Some(view)
+                if same_board(&view.board, &board)
+                    && view.hold == hold
+                    && queue.starts_with(&view.queue) =>
+            {
+                Some(view.queue.len())
+            }
+            _ => None,
+        };
+
+        match known {
+            Some(known) => {
+                for &piece in &queue[known..] {
+                    self.send(&FrontendMessage::NewPiece { piece });
+                }
+            }
+            None => {
+                if self.view.is_some() {
+                    self.send(&FrontendMessage::Stop);
+                }
+                self.send(&FrontendMessage::Start(Start {
+                    hold,
+                    queue: queue.clone(),
+                    combo: game.current_combo(),
+                    back_to_back: game.back_to_back() > 0,
+                    board: tbp::board_cells(&board),
+                }));
+            }
+        }
+
+        self.view = Some(BotView { board, hold, queue });
+    }
+
+    // the first suggested move that can be played, told to the bot
+    fn play(&mut self, game: &Game, moves: &[Move]) -> Option<BotMove> {
+        let state = BotState::of(game)?;
+        let rotation_system = state.piece.tetris_piece_ref().rotation_system;
+        let current = state.piece.tetris_piece_ref().piece_type;
+
+        let (mv, bot_move) = moves.iter().find_map(|mv| {
+            let placement = tbp::placement_of(&mv.location, rotation_system, state.board.rows)?;
+            let hold = placement.piece != current;
+            let bot_move = state.plan_move(hold, placement, &self.options)?;
+            Some((*mv, bot_move))
+        })?;
+
+        self.send(&FrontendMessage::Play { mv });
+        if let Some(view) = self.view.as_mut() {
+            update_view(view, &bot_move, rotation_system);
+        }
+        Some(bot_move)
+    }
+}
+
+fn update_view(
+    view: &mut BotView,
+    bot_move: &BotMove,
+    rotation_system: &'static dyn RotationSystem,
+) {
+    tbp::lock_placement(&mut view.board, &bot_move.placement, rotation_system);
+    tbp::after_play(&mut view.hold, &mut view.queue, bot_move.placement.piece);
+}
+
+fn read_replies<R: io::Read>(output: R, replies: Sender<BotMessage>) {
+    for line in BufReader::new(output).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Cannot read from the bot: {}", e);
+                return;
+            }
+        };
+        match serde_json::from_str(&line) {
+            Ok(message) => {
+                if replies.send(message).is_err() {
+                    return;
+                }
+            }
+            // messages of newer protocol versions are skipped
+            Err(e) => warn!("Ignoring bot message {:?}: {}", line, e),
+        }
+    }
+}
+
+impl MoveSource for TbpFrontend {
+    fn next_move(&mut self, game: &Game) -> Option<BotMove> {
+        self.receive();
+        if self.handshake != Handshake::Ready || game.piece().is_none() {
+            return None;
+        }
+
+        if self.asked_at != Some(game.placed_pieces()) {
+            self.sync(game);
+            self.send(&FrontendMessage::Suggest);
+            self.asked_at = Some(game.placed_pieces());
+            self.outstanding += 1;
+            self.moves = None;
+            return None;
+        }
+
+        let moves = self.moves.take()?;
+        let bot_move = self.play(game, &moves);
+        if bot_move.is_none() {
+            warn!("None of the suggested moves can be played: {:?}", moves);
+            self.asked_at = None;
+        }
+        bot_move
+    }
+
+    fn reset(&mut self) {
+        self.view = None;
+        self.asked_at = None;
+        self.outstanding = 0;
+        self.moves = None;
+    }
+}
+
+impl Drop for TbpFrontend {
+    fn drop(&mut self) {
+        self.send(&FrontendMessage::Quit);
+        if let Some(child) = self.child.as_mut() {
+            if let Err(e) = child.wait() {
+                error!("Cannot wait for the bot to quit: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_tetris_core::{
+        bot::{BotParams, BotPlayer},
+        engine_structs::GameParams,
+        planner::Placement,
+        tbp::{Spin, TbpBot},
+    };
+
+    // our bot on the other side of the protocol, through JSON
+    struct Loopback {
+        bot: TbpBot,
+        replies: Sender<BotMessage>,
+        line: Vec<u8>,
+    }
+
+    impl Write for Loopback {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &byte in buf {
+                if byte != b'\n' {
+                    self.line.push(byte);
+                    continue;
+                }
+                let message = serde_json::from_slice(&self.line).unwrap();
+                self.line.clear();
+                if let Some(reply) = self.bot.handle(message) {
+                    let json = serde_json::to_string(&reply).unwrap();
+                    self.replies
+                        .send(serde_json::from_str(&json).unwrap())
+                        .unwrap();
+                }
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn loopback_frontend(params: &GameParams) -> TbpFrontend {
+        let (sender, replies) = mpsc::channel();
+        let bot_params = BotParams {
+            lookahead: 1,
+            beam_width: 4,
+            ..BotParams::default()
+        };
+        let bot = TbpBot::new(bot_params, params);
+        sender.send(bot.info()).unwrap();
+        let writer = Loopback {
+            bot,
+            replies: sender,
+            line: vec![],
+        };
+        TbpFrontend::new(Box::new(writer), replies)
+    }
+
+    #[test]
+    fn test_message_json() {
+        let json = r#"{"type":"suggestion","moves":[{"location":{"type":"T","orientation":"south","x":1,"y":2},"spin":"full"}],"move_info":{"nodes":3}}"#;
+        let message: BotMessage = serde_json::from_str(json).unwrap();
+
+        match message {
+            BotMessage::Suggestion { moves } => {
+                assert_eq!(moves[0].location.piece, PlayableTetrisPieceType::T);
+                assert_eq!(moves[0].location.y, 2);
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+        assert_eq!(
+            serde_json::to_string(&FrontendMessage::NewPiece {
+                piece: PlayableTetrisPieceType::L
+            })
+            .unwrap(),
+            r#"{"type":"new_piece","piece":"L"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&FrontendMessage::Rules).unwrap(),
+            r#"{"type":"rules"}"#
+        );
+    }
+
+    #[test]
+    fn test_asks_again_after_an_error() {
+        let (sender, replies) = mpsc::channel();
+        let mut frontend = TbpFrontend::new(Box::new(io::sink()), replies);
+        let mut game = Game::new(GameParams::default());
+        game.start();
+        sender.send(BotMessage::Ready).unwrap();
+        frontend.handshake = Handshake::WaitingReady;
+
+        assert_eq!(frontend.next_move(&game), None);
+        sender
+            .send(BotMessage::Error {
+                reason: String::from("no move"),
+            })
+            .unwrap();
+        assert_eq!(frontend.next_move(&game), None);
+        assert_eq!(frontend.next_move(&game), None);
+        assert_eq!(frontend.outstanding, 1);
+
+        let state = BotState::of(&game).unwrap();
+        let piece = state.piece.tetris_piece_ref();
+        let placement = Placement {
+            row: game.board().rows - 2,
+            ..Placement::of(&state.piece)
+        };
+        let location = tbp::location_of(&placement, piece.rotation_system, game.board().rows);
+        let moves = vec![Move {
+            location: location.unwrap(),
+            spin: Spin::None,
+        }];
+        sender.send(BotMessage::Suggestion { moves }).unwrap();
+        assert!(frontend.next_move(&game).is_some());
+    }
+
+    #[test]
+    fn test_plays_the_suggested_moves() {
+        let params = GameParams {
+            seed: Some(5),
+            ..GameParams::default()
+        };
+        let mut player = BotPlayer::new(loopback_frontend(&params));
+        let mut game = Game::new(params);
+        game.start();
+
+        for _ in 0..2000 {
+            if game.placed_pieces() >= 20 || game.game_over().is_some() {
+                break;
+            }
+            let inputs = player.inputs(&game);
+            game.tick(&inputs);
+        }
+
+        assert_eq!(game.game_over(), None);
+        assert_eq!(game.placed_pieces(), 20);
+    }
+}
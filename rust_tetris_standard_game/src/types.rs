@@ -1,4 +0,0 @@
-pub enum TetrisUpdateResult {
-    Continue,
-    GameOver,
-}
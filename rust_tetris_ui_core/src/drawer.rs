@@ -6,27 +6,28 @@ use ggez::{
 
 use rust_tetris_core::{
     board::{TetrisBoard, TetrisCell},
+    engine_structs::{HoldTetrisPiece, TetrisPieceWithPosition},
     enums::TetrisPieceType,
     pieces::TetrisPiece,
 };
 
-use crate::{
-    app_structs::{HoldTetrisPiece, TetrisPieceWithPosition},
-    utils::*,
-};
+use crate::utils::*;
 
 type Scalar = f32;
 
 pub struct Drawer<'a> {
     ctx: &'a mut Context,
     font: Font,
+    layout: BoardLayout,
 }
 
 const SCORE_SIZE: f32 = 24.0;
+const GAME_OVER_SIZE: f32 = 48.0;
+const GARBAGE_METER_W: f32 = 6.0;
 
 impl<'a> Drawer<'a> {
-    pub fn new(ctx: &'a mut Context, font: Font) -> Self {
-        Drawer { ctx, font }
+    pub fn new(ctx: &'a mut Context, font: Font, layout: BoardLayout) -> Self {
+        Drawer { ctx, font, layout }
     }
 
     pub fn try_draw_shadow(
@@ -35,27 +36,24 @@ impl<'a> Drawer<'a> {
         piece: &TetrisPieceWithPosition,
     ) -> GameResult {
         if piece.row() + piece.tetris_piece_ref().height() <= shadow_r {
-            let ps = [
-                BASE_X as Scalar + piece.col() as Scalar * WIDTH,
-                shadow_r as Scalar * WIDTH,
-            ];
-            self.draw_piece_struct(ps, piece.tetris_piece_ref(), true, None)
+            let ps = self.layout.cell_pos(shadow_r, piece.col());
+            let cell = self.layout.cell;
+            self.draw_piece_struct(ps, cell, piece.tetris_piece_ref(), true, None)
         } else {
             Ok(())
         }
     }
 
     pub fn draw_piece_on_board(&mut self, piece: &TetrisPieceWithPosition) -> GameResult {
-        let pp = [
-            BASE_X as Scalar + piece.col() as Scalar * WIDTH,
-            piece.row() as Scalar * WIDTH,
-        ];
-        self.draw_piece_struct(pp, piece.tetris_piece_ref(), false, None)
+        let pp = self.layout.cell_pos(piece.row(), piece.col());
+        let cell = self.layout.cell;
+        self.draw_piece_struct(pp, cell, piece.tetris_piece_ref(), false, None)
     }
 
     fn draw_piece_struct(
         &mut self,
         base: [Scalar; 2],
+        cell: Scalar,
         piece: &TetrisPiece,
         is_shadow: bool,
         override_color: Option<Color>,
@@ -63,55 +61,50 @@ impl<'a> Drawer<'a> {
         for (i, j) in piece.set_cells() {
             let i = i as Scalar;
             let j = j as Scalar;
-            let pos = [j * WIDTH, i * WIDTH];
+            let pos = [j * cell, i * cell];
             let color = override_color
                 .unwrap_or_else(|| playable_piece_to_color(piece.piece_type, is_shadow));
-            self.draw_square_by_pos([base[0] + pos[0], base[1] + pos[1]], WIDTH, color)?
+            self.draw_square_by_pos([base[0] + pos[0], base[1] + pos[1]], cell, color)?
         }
         Ok(())
     }
 
     pub fn draw_hold_piece(&mut self, piece: &HoldTetrisPiece, can_swap: bool) -> GameResult {
-        let pp = [HOLD_X as Scalar, WIDTH];
+        let pp = [self.layout.hold_x(), WIDTH];
         let color = match can_swap {
             true => None,
             false => Some(OTHER_COLOR),
         };
-        self.draw_piece_struct(pp, &piece.piece, false, color)
+        self.draw_piece_struct(pp, WIDTH, &piece.piece, false, color)
     }
 
-    pub fn draw_board(
-        &mut self,
-        base_x: Scalar,
-        base_y: Scalar,
-        piece_board: &TetrisBoard,
-    ) -> GameResult {
-        for i in 0..piece_board.rows {
+    pub fn draw_board(&mut self, piece_board: &TetrisBoard) -> GameResult {
+        for i in self.layout.hidden_rows..piece_board.rows {
             for j in 0..piece_board.cols {
                 if let TetrisCell::FilledCell(p) = piece_board.get(i, j) {
-                    let i = i as isize;
-                    let j = j as isize;
-
-                    self.draw_square_by_index(i as isize, j as isize, p, base_x, base_y)?
+                    self.draw_square_by_index(i, j, p)?
                 }
             }
         }
         Ok(())
     }
 
-    fn draw_square_by_index(
-        &mut self,
-        i: isize,
-        j: isize,
-        piece: TetrisPieceType,
-        base_x: Scalar,
-        base_y: Scalar,
-    ) -> GameResult {
-        let i = i as Scalar;
-        let j = j as Scalar;
+    /// Highlights the completed rows during the line clear delay.
+    pub fn draw_clearing_rows(&mut self, rows: &[isize], cols: isize) -> GameResult {
+        let width = self.layout.cell;
+        let hidden_rows = self.layout.hidden_rows;
+        for &i in rows.iter().filter(|&&i| i >= hidden_rows) {
+            for j in 0..cols {
+                let pos = self.layout.cell_pos(i, j);
+                self.draw_square(pos, width, WHITE)?
+            }
+        }
+        Ok(())
+    }
 
-        let pos = [BASE_X as Scalar + j * WIDTH + base_x, i * WIDTH + base_y];
-        let width = WIDTH;
+    fn draw_square_by_index(&mut self, i: isize, j: isize, piece: TetrisPieceType) -> GameResult {
+        let pos = self.layout.cell_pos(i, j);
+        let width = self.layout.cell;
 
         let color = piece_to_color(piece, false);
         self.draw_square(pos, width, color)
@@ -132,20 +125,54 @@ impl<'a> Drawer<'a> {
     }
 
     pub fn draw_score_text(&mut self, text: &str) -> GameResult {
-        let pp = [TEXT_X as Scalar, (WIN_H as Scalar) - WIDTH * 3.0];
+        let pp = [self.layout.text_x(), WIN_H - WIDTH * 3.0];
         self.draw_text(RED, SCORE_SIZE, text, pp)
     }
 
     pub fn draw_b2b_text(&mut self, current_b2b: u32) -> GameResult {
-        let pp = [TEXT_X as Scalar, (WIN_H as Scalar) - WIDTH];
+        let pp = [self.layout.text_x(), WIN_H - WIDTH];
         self.draw_text(RED, SCORE_SIZE, &format!("B2B: {}x", current_b2b), pp)
     }
 
     pub fn draw_combo(&mut self, current_combo: u32) -> GameResult {
-        let pp = [TEXT_X as Scalar, (WIN_H as Scalar) - WIDTH * 2.0];
+        let pp = [self.layout.text_x(), WIN_H - WIDTH * 2.0];
         self.draw_text(RED, SCORE_SIZE, &format!("Combo: {}", current_combo), pp)
     }
 
+    pub fn draw_stats(&mut self, score: u64, level: u32, lines: u32, apm: f64) -> GameResult {
+        let x = self.layout.text_x();
+        let y = WIDTH * 6.0;
+        self.draw_text(WHITE, SCORE_SIZE, &format!("Score: {}", score), [x, y])?;
+        self.draw_text(
+            WHITE,
+            SCORE_SIZE,
+            &format!("Level: {}", level),
+            [x, y + WIDTH],
+        )?;
+        self.draw_text(
+            WHITE,
+            SCORE_SIZE,
+            &format!("Lines: {}", lines),
+            [x, y + WIDTH * 2.0],
+        )?;
+        self.draw_text(
+            WHITE,
+            SCORE_SIZE,
+            &format!("APM: {:.1}", apm),
+            [x, y + WIDTH * 3.0],
+        )
+    }
+
+    pub fn draw_replay_status(&mut self, seconds: f64, length: f64, speed: f64) -> GameResult {
+        let pp = [self.layout.text_x(), WIDTH * 11.0];
+        self.draw_text(
+            YELLOW,
+            SCORE_SIZE,
+            &format!("Replay {:.0}s / {:.0}s x{}", seconds, length, speed),
+            pp,
+        )
+    }
+
     fn draw_text(&mut self, color: Color, size: f32, text: &str, pos: [Scalar; 2]) -> GameResult {
         let mut text = Text::new(text);
         text.set_font(self.font, Scale::uniform(size));
@@ -157,21 +184,42 @@ impl<'a> Drawer<'a> {
             self.ctx,
             DrawMode::stroke(1.0),
             Rect::new(
-                BASE_X - 1.0,
-                0.0,
-                1.0 * 2.0 + WIDTH * 10.0,
-                1.0 * 2.0 + 600.0,
+                self.layout.base_x - 1.0,
+                self.layout.base_y,
+                1.0 * 2.0 + self.layout.board_w(),
+                1.0 * 2.0 + self.layout.board_h(),
             ),
             YELLOW,
         )?;
         graphics::draw(self.ctx, &border, DrawParam::default())
     }
 
+    pub fn draw_garbage_meter(&mut self, lines: u32) -> GameResult {
+        if lines == 0 {
+            return Ok(());
+        }
+
+        let height = (lines as Scalar * self.layout.cell).min(self.layout.board_h());
+        let bottom = self.layout.base_y + self.layout.board_h();
+        let meter = Mesh::new_rectangle(
+            self.ctx,
+            DrawMode::fill(),
+            Rect::new(
+                self.layout.base_x - GARBAGE_METER_W - 2.0,
+                bottom - height,
+                GARBAGE_METER_W,
+                height,
+            ),
+            RED,
+        )?;
+        graphics::draw(self.ctx, &meter, DrawParam::default())
+    }
+
     pub fn draw_queue_piece(&mut self, index: usize, np: &TetrisPiece) -> GameResult {
         let i = index as Scalar;
         let offset = if i == 0.0 { 0.0 } else { 50.0 };
-        let pos = [BASE_X as Scalar + 355.0 + offset, i * WIDTH * 4.0 + 5.0];
-        self.draw_piece_struct(pos, np, false, None)
+        let pos = [self.layout.queue_x() + offset, i * WIDTH * 4.0 + 5.0];
+        self.draw_piece_struct(pos, WIDTH, np, false, None)
     }
 
     pub fn clear(&mut self) -> GameResult {
@@ -189,4 +237,19 @@ impl<'a> Drawer<'a> {
         )?;
         graphics::draw(self.ctx, &overlay, DrawParam::default())
     }
+
+    pub fn draw_game_over(&mut self, reason: &str) -> GameResult {
+        self.draw_pause()?;
+
+        let x = self.layout.base_x;
+        let y = WIN_H / 2.0 - WIDTH * 2.0;
+        self.draw_text(RED, GAME_OVER_SIZE, "Game Over", [x, y])?;
+        self.draw_text(WHITE, SCORE_SIZE, reason, [x, y + WIDTH * 2.0])?;
+        self.draw_text(
+            WHITE,
+            SCORE_SIZE,
+            "Pause: restart, Quit: exit",
+            [x, y + WIDTH * 3.0],
+        )
+    }
 }
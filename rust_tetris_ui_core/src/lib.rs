@@ -1,3 +1,2 @@
-pub mod app_structs;
 pub mod drawer;
 pub mod utils;
@@ -1,19 +1,16 @@
 use ggez::graphics::Color;
 
 use rust_tetris_core::{
-    constants::Kick,
     enums::{PlayableTetrisPieceType, TetrisPieceType},
     piece,
 };
 
-pub const R: isize = 20;
-pub const C: isize = 10;
 pub const WIDTH: f32 = 30.0;
 pub const WIN_W: f32 = 800.0;
 pub const WIN_H: f32 = 600.0;
-pub const BASE_X: f32 = (WIN_W - (WIDTH * 10.0f32)) / 2.0;
-pub const HOLD_X: f32 = (BASE_X - (WIDTH * 3.0f32)) / 2.0;
-pub const TEXT_X: f32 = BASE_X / 3.0;
+pub const MAX_BOARD_W: f32 = WIN_W / 2.0;
+// hidden rows still drawn above the field, so freshly spawned pieces are visible
+pub const SHOWN_HIDDEN_ROWS: isize = 2;
 pub const GHOST_ALPHA: f32 = 0.3;
 
 pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
@@ -37,6 +34,61 @@ pub const L_COLOR: Color = ORANGE;
 pub const J_COLOR: Color = BLUE;
 pub const OTHER_COLOR: Color = GRAY;
 
+/// Screen placement of a board, derived from its real dimensions.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardLayout {
+    pub hidden_rows: isize,
+    pub visible_rows: isize,
+    pub cols: isize,
+    pub cell: f32,
+    pub base_x: f32,
+    pub base_y: f32,
+}
+
+impl BoardLayout {
+    pub fn new(cols: isize, visible_rows: isize, hidden_rows: isize) -> Self {
+        let cell = WIDTH
+            .min(WIN_H / (visible_rows + SHOWN_HIDDEN_ROWS) as f32)
+            .min(MAX_BOARD_W / cols as f32);
+
+        BoardLayout {
+            hidden_rows,
+            visible_rows,
+            cols,
+            cell,
+            base_x: (WIN_W - cell * cols as f32) / 2.0,
+            base_y: WIN_H - cell * visible_rows as f32,
+        }
+    }
+
+    pub fn board_w(&self) -> f32 {
+        self.cell * self.cols as f32
+    }
+
+    pub fn board_h(&self) -> f32 {
+        self.cell * self.visible_rows as f32
+    }
+
+    pub fn cell_pos(&self, i: isize, j: isize) -> [f32; 2] {
+        [
+            self.base_x + j as f32 * self.cell,
+            self.base_y + (i - self.hidden_rows) as f32 * self.cell,
+        ]
+    }
+
+    pub fn hold_x(&self) -> f32 {
+        (self.base_x - WIDTH * 3.0) / 2.0
+    }
+
+    pub fn queue_x(&self) -> f32 {
+        self.base_x + self.board_w() + 55.0
+    }
+
+    pub fn text_x(&self) -> f32 {
+        self.base_x / 3.0
+    }
+}
+
 fn apply_shadow(original_color: Color, is_shadow: bool) -> Color {
     let mut color = original_color;
 
@@ -69,10 +121,6 @@ pub fn playable_piece_to_color(p: PlayableTetrisPieceType, is_shadow: bool) -> C
     apply_shadow(original_color, is_shadow)
 }
 
-pub fn is_not_empty(kick: Kick) -> bool {
-    kick.0 != 0 || kick.1 != 0
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,7 +138,7 @@ mod tests {
     }
 
     fn get_pairs_with_shadow() -> Vec<(PlayableTetrisPieceType, Color)> {
-        let mut pairs = get_pairs().clone();
+        let mut pairs = get_pairs();
 
         for (_, c) in pairs.iter_mut() {
             c.a = GHOST_ALPHA;
@@ -100,18 +148,28 @@ mod tests {
     }
 
     #[test]
-    fn test_is_not_empty() {
-        let mut kick = (1, 0);
-        assert_eq!(is_not_empty(kick), true);
+    fn test_layout_keeps_spawn_rows_on_screen() {
+        let layout = BoardLayout::new(10, 20, 20);
 
-        kick = (0, 1);
-        assert_eq!(is_not_empty(kick), true);
+        assert_eq!(layout.cell, WIN_H / 22.0);
+        assert!(layout.cell_pos(18, 0)[1].abs() < 0.001);
+        assert!((layout.base_y + layout.board_h() - WIN_H).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_short_layout_keeps_full_cells() {
+        let layout = BoardLayout::new(10, 8, 20);
 
-        kick = (1, 1);
-        assert_eq!(is_not_empty(kick), true);
+        assert_eq!(layout.cell, WIDTH);
+        assert_eq!(layout.cell_pos(20, 0), [250.0, WIN_H - 8.0 * WIDTH]);
+    }
+
+    #[test]
+    fn test_wide_layout_shrinks_cells() {
+        let layout = BoardLayout::new(20, 20, 20);
 
-        kick = (0, 0);
-        assert_eq!(is_not_empty(kick), false);
+        assert_eq!(layout.board_w(), MAX_BOARD_W);
+        assert_eq!(layout.base_x, (WIN_W - MAX_BOARD_W) / 2.0);
     }
 
     #[test]
@@ -174,7 +232,7 @@ mod tests {
         }
 
         let color = piece_to_color(TetrisPieceType::NotPlayable, true);
-        let mut expected_color = OTHER_COLOR.clone();
+        let mut expected_color = OTHER_COLOR;
         expected_color.a = GHOST_ALPHA;
         assert_eq!(
             color,